@@ -0,0 +1,21 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use executable_path_finder::{find_in_path, with_scoped_env};
+
+const SHORT_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+// The same leading entries, padded past the short-PATH fast path so the general search runs.
+const LONG_PATH: &str = "/usr/local/bin:/usr/bin:/bin:/nonexistent/1:/nonexistent/2:/nonexistent/3:/nonexistent/4:/nonexistent/5:/nonexistent/6";
+
+fn bench_find_in_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group("find_in_path");
+    group.bench_function("short PATH", |b| {
+        with_scoped_env(&[("PATH", SHORT_PATH)], || b.iter(|| find_in_path("sh")))
+    });
+    group.bench_function("long PATH", |b| {
+        with_scoped_env(&[("PATH", LONG_PATH)], || b.iter(|| find_in_path("sh")))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_find_in_path);
+criterion_main!(benches);
@@ -0,0 +1,298 @@
+//! Listing every copy of an executable, for shadowing reports, and recording how one was found.
+
+use std::env;
+
+use camino::Utf8PathBuf;
+
+use crate::{
+    binary_candidates, env_path, env_var_names, find_in_cargo_home, find_in_env, normalize_entry,
+    probe_for_binary, trim_exec, Source,
+};
+
+/// A copy of an executable found by [`audit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AuditEntry {
+    pub path: Utf8PathBuf,
+    pub source: Source,
+    /// The zero-based index of the PATH entry, for [`Source::Path`] entries only.
+    pub index: Option<usize>,
+    /// The path with symlinks resolved, or `None` if it couldn't be canonicalized.
+    pub canonical: Option<Utf8PathBuf>,
+}
+
+/// audit return every copy of `exec` that the search sources can see, ordered by precedence like
+/// [`find_with_cargo_home`](crate::find_with_cargo_home): each PATH match in PATH order, then the
+/// file the environment variable points to, then the one in `$CARGO_HOME/bin`. The first entry is
+/// the one that is used, every later one is shadowed by it.
+///
+/// A PATH entry listed twice yields an entry per index, with the same path. Entries sharing a
+/// `canonical` target are symlinks to (or repeated entries of) the same file, not true
+/// duplicates. The environment variable is only listed when it points to an existing file.
+pub fn audit(exec: &str) -> Vec<AuditEntry> {
+    let exec = trim_exec(exec);
+    let paths = env::var_os("PATH").unwrap_or_default();
+    let path_matches = env::split_paths(&paths)
+        .enumerate()
+        .filter_map(|(index, dir)| Some((Utf8PathBuf::try_from(dir).ok()?, index)))
+        .filter_map(|(dir, index)| {
+            let path = probe_for_binary(normalize_entry(dir).join(exec))?;
+            Some((path, Source::Path, Some(index)))
+        });
+    let env_match = find_in_env(exec)
+        .filter(|it| it.is_file())
+        .map(|path| (path, Source::Env, None));
+    let cargo_home_match = find_in_cargo_home(exec).map(|path| (path, Source::CargoHome, None));
+
+    path_matches
+        .chain(env_match)
+        .chain(cargo_home_match)
+        .map(|(path, source, index)| AuditEntry {
+            canonical: path.canonicalize_utf8().ok(),
+            path,
+            source,
+            index,
+        })
+        .collect()
+}
+
+/// The record of a search produced by [`find_audited`]: every attempt in the order it was made,
+/// and the path that was chosen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuditLog {
+    /// The searched executable, as given.
+    pub exec: String,
+    pub attempts: Vec<AuditAttempt>,
+    /// The path of the [`AuditOutcome::Found`] attempt, if any.
+    pub chosen: Option<Utf8PathBuf>,
+}
+
+/// One step of an [`AuditLog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuditAttempt {
+    pub source: Source,
+    /// The PATH entry, for [`Source::Path`] attempts whose entry is UTF-8.
+    pub dir: Option<Utf8PathBuf>,
+    /// The environment variable, for [`Source::Env`] attempts.
+    pub variable: Option<String>,
+    /// The file that was probed, one per extension tried, or the environment variable's value.
+    pub candidate: Option<Utf8PathBuf>,
+    pub outcome: AuditOutcome,
+}
+
+/// What an [`AuditAttempt`] led to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AuditOutcome {
+    /// The candidate was chosen; this is the last attempt of the log.
+    Found,
+    /// Nothing exists at the candidate.
+    Missing,
+    /// The candidate exists but isn't a file, e.g. a directory.
+    NotAFile,
+    /// The PATH entry or the environment variable isn't valid UTF-8, so it was skipped.
+    NonUtf8,
+    /// The PATH entry normalizes to one already searched, so it was skipped.
+    Duplicate,
+    /// The environment variable isn't set.
+    Unset,
+}
+
+/// find_audited return what [`find`](crate::find) returns for `exec`, together with an
+/// [`AuditLog`] of how it was resolved, intended to be persisted as an audit trail.
+///
+/// The log is produced whether or not a match is found, unlike the error of
+/// [`try_find`](crate::try_find) which only explains a failure. Its attempts follow the search
+/// order: each PATH entry in order, with one attempt per candidate file (the bare name, or on
+/// Windows each `PATHEXT` extension, then the bare name), then each environment variable named
+/// after `exec`. PATH entries that can't be searched get a single attempt saying why. The log
+/// stops at the chosen attempt, so later sources that would have been shadowed aren't listed;
+/// see [`audit`] for those. Like [`find_in_env`], the environment variable's value is chosen
+/// without checking that it exists. Overrides installed with the `test-util` feature aren't
+/// consulted.
+///
+/// With the `serde` feature, the log serializes as
+/// `{ "exec", "attempts": [{ "source", "dir", "variable", "candidate", "outcome" }], "chosen" }`.
+pub fn find_audited(exec: &str) -> (Option<Utf8PathBuf>, AuditLog) {
+    let mut attempts = Vec::new();
+    let chosen = audited_path(trim_exec(exec), &mut attempts)
+        .or_else(|| audited_env(trim_exec(exec), &mut attempts));
+    let log = AuditLog {
+        exec: exec.to_owned(),
+        attempts,
+        chosen: chosen.clone(),
+    };
+    (chosen, log)
+}
+
+fn audited_path(exec: &str, attempts: &mut Vec<AuditAttempt>) -> Option<Utf8PathBuf> {
+    let attempt = |dir, candidate, outcome| AuditAttempt {
+        source: Source::Path,
+        dir,
+        variable: None,
+        candidate,
+        outcome,
+    };
+    let paths = env::var_os("PATH").unwrap_or_default();
+    let mut searched = Vec::new();
+    for dir in env::split_paths(&paths) {
+        let Ok(dir) = Utf8PathBuf::try_from(dir) else {
+            attempts.push(attempt(None, None, AuditOutcome::NonUtf8));
+            continue;
+        };
+        // Mirrors the normalization and deduplication of the PATH search.
+        let normalized = normalize_entry(dir.clone());
+        if searched.contains(&normalized) {
+            attempts.push(attempt(Some(dir), None, AuditOutcome::Duplicate));
+            continue;
+        }
+        for candidate in binary_candidates(normalized.join(exec)) {
+            let outcome = match candidate.metadata() {
+                Ok(metadata) if metadata.is_file() => AuditOutcome::Found,
+                Ok(_) => AuditOutcome::NotAFile,
+                Err(_) => AuditOutcome::Missing,
+            };
+            attempts.push(attempt(Some(dir.clone()), Some(candidate.clone()), outcome));
+            if outcome == AuditOutcome::Found {
+                return Some(candidate);
+            }
+        }
+        searched.push(normalized);
+    }
+    None
+}
+
+fn audited_env(exec: &str, attempts: &mut Vec<AuditAttempt>) -> Option<Utf8PathBuf> {
+    for variable in env_var_names(exec) {
+        let value = env::var_os(&variable);
+        let candidate = value.clone().map(env_path).and_then(Result::ok);
+        let outcome = match (&value, &candidate) {
+            (None, _) => AuditOutcome::Unset,
+            (Some(_), None) => AuditOutcome::NonUtf8,
+            (Some(_), Some(_)) => AuditOutcome::Found,
+        };
+        attempts.push(AuditAttempt {
+            source: Source::Env,
+            dir: None,
+            variable: Some(variable),
+            candidate: candidate.clone(),
+            outcome,
+        });
+        // Like `find_in_env`, the first variable that is set decides, even when it isn't UTF-8.
+        if value.is_some() {
+            return candidate;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_audit() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().canonicalize().unwrap()).unwrap();
+        let name = format!("fake-audit-binary{}", env::consts::EXE_SUFFIX);
+        let [first, second, cargo_bin] = [
+            dir.join("first"),
+            dir.join("second"),
+            dir.join("cargo").join("bin"),
+        ];
+        for sub in [&first, &second, &cargo_bin] {
+            fs::create_dir_all(sub).unwrap();
+            fs::write(sub.join(&name), "").unwrap();
+        }
+
+        let paths = env::join_paths([dir.join("missing"), first.clone(), second.clone()]).unwrap();
+        let env_binary = second.join(&name);
+        let cargo_home = dir.join("cargo");
+        let vars = [
+            ("PATH", paths.to_str().unwrap()),
+            ("FAKE-AUDIT-BINARY", env_binary.as_str()),
+            ("CARGO_HOME", cargo_home.as_str()),
+        ];
+        with_scoped_env(&vars, || {
+            let entry = |sub: &Utf8PathBuf, source, index| AuditEntry {
+                path: sub.join(&name),
+                source,
+                index,
+                canonical: Some(sub.join(&name)),
+            };
+            assert_eq!(
+                audit("fake-audit-binary"),
+                [
+                    entry(&first, Source::Path, Some(1)),
+                    entry(&second, Source::Path, Some(2)),
+                    entry(&second, Source::Env, None),
+                    entry(&cargo_bin, Source::CargoHome, None),
+                ]
+            );
+            assert!(audit("non-existent-binary").is_empty());
+        });
+    }
+
+    #[test]
+    fn test_find_audited() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let [missing, shadowing, found] = ["missing", "shadowing", "found"].map(|it| dir.join(it));
+        fs::create_dir_all(shadowing.join("fake-audited-binary")).unwrap();
+        fs::create_dir_all(&found).unwrap();
+        let binary = found.join(format!("fake-audited-binary{}", env::consts::EXE_SUFFIX));
+        fs::write(&binary, "").unwrap();
+
+        let paths = env::join_paths([&missing, &shadowing, &missing, &found, &shadowing]).unwrap();
+        let vars = [
+            ("PATH", paths.to_str().unwrap()),
+            ("FAKE-AUDITED-ENV", "/from-env"),
+        ];
+        with_scoped_env(&vars, || {
+            let (path, log) = find_audited("fake-audited-binary");
+            assert_eq!(path, Some(binary.clone()));
+            assert_eq!(log.chosen, path);
+            let outcomes = |dir: &Utf8PathBuf| -> Vec<_> {
+                log.attempts
+                    .iter()
+                    .filter(|it| it.dir.as_ref() == Some(dir))
+                    .map(|it| it.outcome)
+                    .collect()
+            };
+            assert!(outcomes(&missing)[..outcomes(&missing).len() - 1]
+                .iter()
+                .all(|it| *it == AuditOutcome::Missing));
+            assert_eq!(outcomes(&missing).last(), Some(&AuditOutcome::Duplicate));
+            assert_eq!(outcomes(&shadowing)[0], AuditOutcome::NotAFile);
+            assert_eq!(outcomes(&found).last(), Some(&AuditOutcome::Found));
+            let last = log.attempts.last().unwrap();
+            assert_eq!(last.candidate, Some(binary.clone()));
+            assert!(log.attempts.iter().all(|it| it.source == Source::Path));
+
+            let (path, log) = find_audited("fake-audited-env");
+            assert_eq!(path, Some("/from-env".into()));
+            let last = log.attempts.last().unwrap();
+            assert_eq!(last.source, Source::Env);
+            assert_eq!(last.variable.as_deref(), Some("FAKE-AUDITED-ENV"));
+            assert_eq!(last.outcome, AuditOutcome::Found);
+
+            let (path, log) = find_audited("non-existent-binary");
+            assert_eq!(path, None);
+            assert_eq!(log.chosen, None);
+            assert_eq!(log.exec, "non-existent-binary");
+            assert!(log
+                .attempts
+                .iter()
+                .all(|it| it.outcome != AuditOutcome::Found));
+            assert_eq!(log.attempts.last().unwrap().outcome, AuditOutcome::Unset);
+        });
+    }
+}
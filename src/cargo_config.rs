@@ -0,0 +1,304 @@
+//! Resolving executables overridden in Cargo's configuration files.
+
+use std::{env, fs};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{env_var_names, find, find_with_cargo_home, get_cargo_home, trim_exec};
+
+// An executable set by a config file, before resolving it against the file's location.
+enum Override {
+    // `build.<exec>`, e.g. `build.rustc`.
+    Program(String),
+    // `env.<VAR>`, as a string or a `{ value = "...", relative = true }` table.
+    Env { value: String, relative: bool },
+}
+
+/// find_from_cargo_config return the executable configured for `exec` in Cargo's configuration
+/// files (see <https://doc.rust-lang.org/cargo/reference/config.html>), falling back to
+/// [`find_with_cargo_home`] (PATH, environment variables and CARGO_HOME) when none is.
+///
+/// The files are discovered like Cargo does: `.cargo/config.toml` (or the legacy `.cargo/config`)
+/// in the current directory and each of its parents, then `$CARGO_HOME/config.toml`. The first
+/// file, from the current directory up, that sets one of these keys wins:
+/// 1) `build.<exec>`, such as `build.rustc`, `build.rustdoc` or `build.rustc-wrapper`.
+///    A value with a path separator is relative to the directory containing `.cargo`, and a bare
+///    name is resolved with [`find`]. Like Cargo, the process environment overrides this key:
+///    when the variable named after `exec` (`RUSTC`, `RUSTC_WRAPPER`, ...) or `CARGO_BUILD_<EXEC>`
+///    is set, its value is returned as-is instead.
+/// 2) `env.<VAR>`, where `VAR` is named after `exec` like in [`find_in_env`](crate::find_in_env).
+///    Like Cargo, the entry is skipped when the variable is already set in the process environment,
+///    unless it has `force = true`; with `relative = true`, the value is relative to the directory
+///    containing `.cargo`.
+///
+/// A configured path is returned as-is, without checking that it exists, like a path from an
+/// environment variable. Only the subset of TOML used for these keys is understood: tables,
+/// dotted keys, strings and inline tables.
+pub fn find_from_cargo_config(exec: &str) -> Option<Utf8PathBuf> {
+    let cwd = env::current_dir()
+        .ok()
+        .and_then(|it| Utf8PathBuf::try_from(it).ok());
+    find_from_cargo_config_in(cwd.as_deref(), exec)
+}
+
+fn find_from_cargo_config_in(cwd: Option<&Utf8Path>, exec: &str) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    config_files(cwd)
+        .into_iter()
+        .find_map(|path| {
+            let contents = fs::read_to_string(&path).ok()?;
+            // The directory containing `.cargo`, or CARGO_HOME's parent.
+            let root = path.parent()?.parent()?;
+            match configured(&contents, exec)? {
+                Override::Program(value) if value.contains(['/', '\\']) => Some(root.join(value)),
+                Override::Program(value) => find(&value),
+                Override::Env { value, relative } if relative => Some(root.join(value)),
+                Override::Env { value, .. } => Some(value.into()),
+            }
+        })
+        .or_else(|| find_with_cargo_home(exec))
+}
+
+// The config files in precedence order, most specific first.
+fn config_files(cwd: Option<&Utf8Path>) -> Vec<Utf8PathBuf> {
+    let mut files: Vec<_> = cwd
+        .into_iter()
+        .flat_map(Utf8Path::ancestors)
+        .filter_map(|dir| config_in(&dir.join(".cargo")))
+        .collect();
+    if let Some(file) = get_cargo_home().and_then(|it| config_in(&it)) {
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files
+}
+
+// Cargo uses the legacy `config` when both it and `config.toml` exist.
+fn config_in(dir: &Utf8Path) -> Option<Utf8PathBuf> {
+    ["config", "config.toml"]
+        .into_iter()
+        .map(|name| dir.join(name))
+        .find(|it| it.is_file())
+}
+
+fn configured(contents: &str, exec: &str) -> Option<Override> {
+    let entries = entries(contents);
+    let program = entries
+        .iter()
+        .find_map(|(key, value)| match key.as_slice() {
+            [table, name] if table == "build" && name == exec => {
+                let value = string(value)?.0;
+                Some(match build_env(exec) {
+                    Some(value) => Override::Env {
+                        value,
+                        relative: false,
+                    },
+                    None => Override::Program(value),
+                })
+            }
+            _ => None,
+        });
+    program.or_else(|| {
+        let names = env_var_names(exec);
+        entries
+            .iter()
+            .find_map(|(key, value)| match key.as_slice() {
+                [table, name] if table == "env" && names.contains(name) => {
+                    env_override(name, value)
+                }
+                _ => None,
+            })
+    })
+}
+
+// The process environment variable overriding `build.<exec>`, checked in Cargo's order.
+fn build_env(exec: &str) -> Option<String> {
+    let underscored = exec.to_ascii_uppercase().replace('-', "_");
+    let mut names = env_var_names(exec);
+    names.extend([underscored.clone(), format!("CARGO_BUILD_{underscored}")]);
+    names.into_iter().find_map(|name| env::var(name).ok())
+}
+
+fn env_override(name: &str, value: &str) -> Option<Override> {
+    if let Some((value, _)) = string(value) {
+        return env::var_os(name).is_none().then_some(Override::Env {
+            value,
+            relative: false,
+        });
+    }
+    let fields = inline_table(value)?;
+    let field = |key: &str| {
+        fields
+            .iter()
+            .find(|(it, _)| it == key)
+            .map(|(_, it)| it.as_str())
+    };
+    let force = field("force") == Some("true");
+    if !force && env::var_os(name).is_some() {
+        return None;
+    }
+    Some(Override::Env {
+        value: field("value")?.to_owned(),
+        relative: field("relative") == Some("true"),
+    })
+}
+
+// Every `key = value` line of a TOML document as the full key (prefixed with its table) and the
+// unparsed value. Multi-line values aren't supported.
+fn entries(contents: &str) -> Vec<(Vec<String>, &str)> {
+    let mut table = Vec::new();
+    let mut entries = Vec::new();
+    for line in contents.lines().map(str::trim) {
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.trim_start_matches('[');
+            if let Some((header, _)) = header.split_once(']') {
+                table = key_parts(header);
+            }
+        } else if let Some((key, value)) = line.split_once('=').filter(|_| !line.starts_with('#')) {
+            let key = table.iter().cloned().chain(key_parts(key)).collect();
+            entries.push((key, value.trim()));
+        }
+    }
+    entries
+}
+
+fn key_parts(key: &str) -> Vec<String> {
+    key.split('.')
+        .map(|it| it.trim().trim_matches(['"', '\'']).to_owned())
+        .collect()
+}
+
+// A basic (`"..."`) or literal (`'...'`) string at the start of `value`, and what follows it.
+fn string(value: &str) -> Option<(String, &str)> {
+    if let Some(rest) = value.strip_prefix('\'') {
+        let (literal, rest) = rest.split_once('\'')?;
+        return Some((literal.to_owned(), rest));
+    }
+    let rest = value.strip_prefix('"')?;
+    let mut parsed = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((parsed, &rest[i + 1..])),
+            '\\' => parsed.push(match chars.next()?.1 {
+                'n' => '\n',
+                't' => '\t',
+                escaped => escaped,
+            }),
+            c => parsed.push(c),
+        }
+    }
+    None
+}
+
+// The fields of an inline table such as `{ value = "bin/tool", relative = true }`, with strings
+// unquoted and other values as written.
+fn inline_table(value: &str) -> Option<Vec<(String, String)>> {
+    let mut rest = value.strip_prefix('{')?;
+    let mut fields = Vec::new();
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        if rest.starts_with('}') {
+            return Some(fields);
+        }
+        let (key, value) = rest.split_once('=')?;
+        let value = value.trim_start();
+        let (value, tail) = match string(value) {
+            Some(it) => it,
+            None => {
+                let end = value.find([',', '}'])?;
+                (value[..end].trim().to_owned(), &value[end..])
+            }
+        };
+        fields.push((key.trim().trim_matches(['"', '\'']).to_owned(), value));
+        rest = tail;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_string() {
+        assert_eq!(string(r#""a\"b" # c"#), Some(("a\"b".to_owned(), " # c")));
+        assert_eq!(
+            string(r"'C:\tools\x.exe'"),
+            Some((r"C:\tools\x.exe".to_owned(), ""))
+        );
+        assert_eq!(string("true"), None);
+        assert_eq!(string("\"unterminated"), None);
+    }
+
+    #[test]
+    fn test_find_from_cargo_config() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let project = dir.join("project");
+        let cargo_home = dir.join("cargo");
+        fs::create_dir_all(project.join(".cargo")).unwrap();
+        fs::create_dir_all(project.join("sub")).unwrap();
+        fs::create_dir_all(&cargo_home).unwrap();
+        fs::write(
+            project.join(".cargo/config.toml"),
+            r#"# Project overrides
+[build]
+rustc = "tools/rustc" # relative to the project
+
+[env]
+FAKE-CONFIG-TOOL = { value = "bin/tool", relative = true }
+FAKE-FORCED = { value = "/forced", force = true }
+FAKE-UNFORCED = "/unforced"
+"#,
+        )
+        .unwrap();
+        fs::write(
+            cargo_home.join("config.toml"),
+            "env.FAKE-HOME-TOOL = '/home-tool'\n[env]\nFAKE-CONFIG-TOOL = \"/shadowed\"\n",
+        )
+        .unwrap();
+
+        let vars = [
+            ("PATH", dir.as_str()),
+            ("CARGO_HOME", cargo_home.as_str()),
+            ("FAKE-FORCED", "/from-env"),
+            ("FAKE-UNFORCED", "/from-env"),
+        ];
+        with_scoped_env(&vars, || {
+            let find = |exec| find_from_cargo_config_in(Some(&project.join("sub")), exec);
+            assert_eq!(find("rustc"), Some(project.join("tools/rustc")));
+            assert_eq!(find("fake-config-tool"), Some(project.join("bin/tool")));
+            assert_eq!(find("fake-forced"), Some("/forced".into()));
+            assert_eq!(find("fake-unforced"), Some("/from-env".into()));
+            assert_eq!(find("fake-home-tool"), Some("/home-tool".into()));
+            assert_eq!(find("non-existent-binary"), None);
+
+            let find = |exec| find_from_cargo_config_in(Some(&dir), exec);
+            assert_eq!(find("fake-config-tool"), Some("/shadowed".into()));
+        });
+
+        let vars = [
+            ("PATH", dir.as_str()),
+            ("CARGO_HOME", cargo_home.as_str()),
+            ("RUSTC", "/env/rustc"),
+        ];
+        with_scoped_env(&vars, || {
+            let find = |exec| find_from_cargo_config_in(Some(&project), exec);
+            assert_eq!(find("rustc"), Some("/env/rustc".into()));
+        });
+        let vars = [
+            ("PATH", dir.as_str()),
+            ("CARGO_HOME", cargo_home.as_str()),
+            ("CARGO_BUILD_RUSTC", "/env/cargo-build-rustc"),
+        ];
+        with_scoped_env(&vars, || {
+            let find = |exec| find_from_cargo_config_in(Some(&project), exec);
+            assert_eq!(find("rustc"), Some("/env/cargo-build-rustc".into()));
+        });
+    }
+}
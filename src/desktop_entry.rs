@@ -0,0 +1,184 @@
+//! Resolving freedesktop.org application names to the binaries their desktop entries run.
+
+use std::{env, fs};
+
+use camino::Utf8PathBuf;
+
+use crate::{find, home};
+
+/// find_from_desktop_entry return the executable run by the desktop entry `name` (e.g. `firefox`,
+/// `org.gnome.Nautilus` or `firefox.desktop`), resolving GUI app names to runnable binaries.
+///
+/// `<name>.desktop` is looked up in the `applications` subdirectory of `$XDG_DATA_HOME`
+/// (`~/.local/share` when unset) and then of each entry of `$XDG_DATA_DIRS`
+/// (`/usr/local/share:/usr/share` when unset), the first file found winning as in the XDG spec.
+/// Entries in nested directories of `applications` aren't searched. The `Exec=` key of the
+/// `[Desktop Entry]` group is split into arguments, honoring double quotes and `\` escapes; field
+/// codes such as `%U` or `%f` are dropped, as is a leading `env` with its `NAME=value`
+/// assignments. The first remaining argument is the program: an absolute path is returned if it
+/// is a file, and a bare name is resolved with [`find`].
+pub fn find_from_desktop_entry(name: &str) -> Option<Utf8PathBuf> {
+    let file_name = if name.ends_with(".desktop") {
+        name.to_owned()
+    } else {
+        format!("{name}.desktop")
+    };
+    let contents = application_dirs()
+        .into_iter()
+        .find_map(|dir| fs::read_to_string(dir.join(&file_name)).ok())?;
+    let program = exec_program(&exec_key(&contents)?)?;
+    if program.starts_with('/') {
+        Some(Utf8PathBuf::from(program)).filter(|it| it.is_file())
+    } else {
+        find(&program)
+    }
+}
+
+fn application_dirs() -> Vec<Utf8PathBuf> {
+    let var = |key| {
+        env::var(key)
+            .ok()
+            .filter(|it| !it.is_empty())
+            .map(Utf8PathBuf::from)
+    };
+    let data_home = var("XDG_DATA_HOME").or_else(|| {
+        let home = Utf8PathBuf::try_from(home::home_dir()?).ok()?;
+        Some(home.join(".local/share"))
+    });
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|it| !it.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_owned());
+    data_home
+        .into_iter()
+        .chain(
+            data_dirs
+                .split(':')
+                .filter(|it| !it.is_empty())
+                .map(Utf8PathBuf::from),
+        )
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+// The value of `Exec=` in the `[Desktop Entry]` group.
+fn exec_key(contents: &str) -> Option<String> {
+    let mut in_entry = false;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+        } else if let Some(value) = line.strip_prefix("Exec").filter(|_| in_entry) {
+            if let Some(value) = value.trim_start().strip_prefix('=') {
+                return Some(value.trim().to_owned());
+            }
+        }
+    }
+    None
+}
+
+// The program of an `Exec=` value, see `find_from_desktop_entry`.
+fn exec_program(exec: &str) -> Option<String> {
+    let mut args = split_args(exec).into_iter().filter(|it| !is_field_code(it));
+    let mut program = args.next()?;
+    if program == "env" {
+        program = args.find(|it| !it.contains('='))?;
+    }
+    Some(program.replace("%%", "%"))
+}
+
+fn is_field_code(arg: &str) -> bool {
+    let mut chars = arg.chars();
+    chars.next() == Some('%') && chars.next().is_some_and(|it| it != '%') && chars.next().is_none()
+}
+
+fn split_args(exec: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                arg.get_or_insert_with(String::new);
+            }
+            '\\' if quoted => arg.get_or_insert_with(String::new).extend(chars.next()),
+            c if c.is_whitespace() && !quoted => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_exec_program() {
+        assert_eq!(exec_program("firefox %u").as_deref(), Some("firefox"));
+        assert_eq!(
+            exec_program(r#""/opt/My App/app" --flag %F"#).as_deref(),
+            Some("/opt/My App/app")
+        );
+        assert_eq!(
+            exec_program("env GDK_BACKEND=x11 FOO=1 app %U").as_deref(),
+            Some("app")
+        );
+        assert_eq!(exec_program("%U"), None);
+        assert_eq!(exec_program(""), None);
+    }
+
+    #[test]
+    fn test_find_from_desktop_entry() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let [data_home, data_dir, bin] = [dir.join("home"), dir.join("share"), dir.join("bin")];
+        for applications in [
+            data_home.join("applications"),
+            data_dir.join("applications"),
+        ] {
+            fs::create_dir_all(applications).unwrap();
+        }
+        fs::create_dir(&bin).unwrap();
+        fs::write(bin.join("fake-app"), "").unwrap();
+        fs::write(bin.join("fake-user-app"), "").unwrap();
+        fs::write(
+            data_dir.join("applications/org.example.App.desktop"),
+            "[Desktop Entry]\nName=App\nExec=fake-app %U\n\n[Desktop Action new]\nExec=other\n",
+        )
+        .unwrap();
+        fs::write(
+            data_dir.join("applications/org.example.Shadowed.desktop"),
+            format!("[Desktop Entry]\nExec={bin}/fake-app\n"),
+        )
+        .unwrap();
+        fs::write(
+            data_home.join("applications/org.example.Shadowed.desktop"),
+            "[Desktop Entry]\nExec = env FOO=1 fake-user-app\n",
+        )
+        .unwrap();
+
+        let vars = [
+            ("PATH", bin.as_str()),
+            ("XDG_DATA_HOME", data_home.as_str()),
+            ("XDG_DATA_DIRS", data_dir.as_str()),
+        ];
+        with_scoped_env(&vars, || {
+            assert_eq!(
+                find_from_desktop_entry("org.example.App"),
+                Some(bin.join("fake-app"))
+            );
+            assert_eq!(
+                find_from_desktop_entry("org.example.Shadowed.desktop"),
+                Some(bin.join("fake-user-app"))
+            );
+            assert_eq!(find_from_desktop_entry("org.example.Missing"), None);
+        });
+    }
+}
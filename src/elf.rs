@@ -0,0 +1,422 @@
+//! Telling statically-linked ELF executables apart from dynamically-linked ones.
+
+use std::{env, fs, fs::File, io::Read};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{find, find_all};
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_STRTAB: u64 = 5;
+const DT_RPATH: u64 = 15;
+const DT_RUNPATH: u64 = 29;
+
+// Enough for the ELF header and the program header table of any ordinary executable, which
+// directly follows it.
+const MAX_HEADERS_LEN: u64 = 64 * 1024;
+
+/// is_static return whether `path` is a statically-linked ELF executable, i.e. an ELF file without
+/// a program interpreter (`PT_INTERP`) segment.
+///
+/// Only the ELF header and program headers are read. Anything that isn't ELF, such as a script or
+/// a Mach-O binary on macOS, is reported as not static, as is a file that can't be read or is
+/// truncated.
+pub fn is_static(path: &Utf8Path) -> bool {
+    let mut contents = Vec::new();
+    let read =
+        File::open(path).and_then(|file| file.take(MAX_HEADERS_LEN).read_to_end(&mut contents));
+    read.is_ok()
+        && Elf::parse(&contents)
+            .and_then(|elf| elf.program_headers())
+            .is_some_and(|headers| headers.iter().all(|it| it.ty != PT_INTERP))
+}
+
+/// find_static return the first statically-linked match for `exec` on PATH, as told by
+/// [`is_static`], falling back to the first match when none is, e.g. to pick a binary that can be
+/// copied into a minimal container.
+///
+/// Every match from [`find_all`] is read, so this is slower than [`find`](crate::find). On
+/// platforms whose executables aren't ELF, no match is static and this is equivalent to the first
+/// PATH match.
+pub fn find_static(exec: &str) -> Option<Utf8PathBuf> {
+    let matches = find_all(exec);
+    let first = matches.first().cloned();
+    matches.into_iter().find(|it| is_static(it)).or(first)
+}
+
+/// find_with_deps return the executable resolved by [`find`] together with the shared libraries
+/// it directly depends on, e.g. to bundle them with it.
+///
+/// The dependencies are the `DT_NEEDED` entries of the ELF dynamic section, in order, resolved
+/// like the dynamic loader does: in the `DT_RPATH` directories (only when there is no
+/// `DT_RUNPATH`), then `LD_LIBRARY_PATH`, then `DT_RUNPATH`, with `$ORIGIN` expanded to the
+/// executable's directory, and finally the default directories (`/lib`, `/usr/lib`, their `64`
+/// and multiarch `<arch>-linux-gnu` variants). `/etc/ld.so.cache` isn't read, so a library
+/// only found through it, and any other library that can't be located, is reported as its bare
+/// soname. Dependencies of dependencies aren't listed.
+///
+/// Only ELF is understood: a script, a Mach-O binary on macOS, or a static executable yields no
+/// dependencies. The whole file is read to parse the dynamic section.
+pub fn find_with_deps(exec: &str) -> Option<(Utf8PathBuf, Vec<Utf8PathBuf>)> {
+    let path = find(exec)?;
+    let contents = fs::read(&path).unwrap_or_default();
+    let Some(dynamic) = Elf::parse(&contents).and_then(|elf| elf.dynamic()) else {
+        return Some((path, Vec::new()));
+    };
+    let origin = path.parent().map(Utf8Path::as_str).unwrap_or(".");
+    let search_path = |list: &Option<String>| -> Vec<Utf8PathBuf> {
+        list.iter()
+            .flat_map(|it| it.split(':'))
+            .filter(|it| !it.is_empty())
+            .map(|it| {
+                it.replace("${ORIGIN}", origin)
+                    .replace("$ORIGIN", origin)
+                    .into()
+            })
+            .collect()
+    };
+    let ld_library_path = env::var("LD_LIBRARY_PATH").ok();
+    let mut dirs = Vec::new();
+    if dynamic.runpath.is_none() {
+        dirs.extend(search_path(&dynamic.rpath));
+    }
+    dirs.extend(search_path(&ld_library_path));
+    dirs.extend(search_path(&dynamic.runpath));
+    dirs.extend(default_library_dirs());
+
+    let deps = dynamic
+        .needed
+        .into_iter()
+        .map(|soname| {
+            dirs.iter()
+                .map(|dir| dir.join(&soname))
+                .find(|it| it.is_file())
+                .unwrap_or_else(|| soname.into())
+        })
+        .collect();
+    Some((path, deps))
+}
+
+fn default_library_dirs() -> Vec<Utf8PathBuf> {
+    let multiarch = format!("{}-linux-gnu", env::consts::ARCH);
+    ["/lib", "/usr/lib"]
+        .into_iter()
+        .flat_map(|dir| {
+            [
+                format!("{dir}/{multiarch}"),
+                format!("{dir}64"),
+                dir.to_owned(),
+            ]
+        })
+        .map(Utf8PathBuf::from)
+        .collect()
+}
+
+// The header fields of an ELF file that this module needs.
+struct Elf<'a> {
+    contents: &'a [u8],
+    is_64: bool,
+    little_endian: bool,
+}
+
+struct ProgramHeader {
+    ty: u32,
+    offset: u64,
+    vaddr: u64,
+    filesz: u64,
+}
+
+// The dynamic section entries used by `find_with_deps`, with strings resolved.
+struct Dynamic {
+    needed: Vec<String>,
+    rpath: Option<String>,
+    runpath: Option<String>,
+}
+
+impl<'a> Elf<'a> {
+    // `None` if `contents` doesn't start with an ELF header.
+    fn parse(contents: &'a [u8]) -> Option<Self> {
+        if contents.get(..4)? != b"\x7fELF" {
+            return None;
+        }
+        let is_64 = match contents.get(4)? {
+            1 => false,
+            2 => true,
+            _ => return None,
+        };
+        let little_endian = match contents.get(5)? {
+            1 => true,
+            2 => false,
+            _ => return None,
+        };
+        Some(Elf {
+            contents,
+            is_64,
+            little_endian,
+        })
+    }
+
+    fn read(&self, offset: u64, len: usize) -> Option<u64> {
+        let offset = usize::try_from(offset).ok()?;
+        let bytes = self.contents.get(offset..offset.checked_add(len)?)?;
+        let fold = |acc: u64, byte: &u8| acc << 8 | u64::from(*byte);
+        Some(if self.little_endian {
+            bytes.iter().rev().fold(0, fold)
+        } else {
+            bytes.iter().fold(0, fold)
+        })
+    }
+
+    // A field that is 8 bytes long in 64-bit files and 4 bytes long in 32-bit ones.
+    fn read_word(&self, offset: u64) -> Option<u64> {
+        self.read(offset, if self.is_64 { 8 } else { 4 })
+    }
+
+    // `None` if the program header table is truncated.
+    fn program_headers(&self) -> Option<Vec<ProgramHeader>> {
+        let (phoff, phentsize, phnum) = if self.is_64 {
+            (
+                self.read(0x20, 8)?,
+                self.read(0x36, 2)?,
+                self.read(0x38, 2)?,
+            )
+        } else {
+            (
+                self.read(0x1c, 4)?,
+                self.read(0x2a, 2)?,
+                self.read(0x2c, 2)?,
+            )
+        };
+        (0..phnum)
+            .map(|i| {
+                let header = phoff.checked_add(i * phentsize)?;
+                // `p_offset`, `p_vaddr` and `p_filesz`, which 64-bit files place after `p_flags`.
+                let [offset, vaddr, filesz] = if self.is_64 { [8, 16, 32] } else { [4, 8, 16] };
+                Some(ProgramHeader {
+                    ty: self.read(header, 4)? as u32,
+                    offset: self.read_word(header.checked_add(offset)?)?,
+                    vaddr: self.read_word(header.checked_add(vaddr)?)?,
+                    filesz: self.read_word(header.checked_add(filesz)?)?,
+                })
+            })
+            .collect()
+    }
+
+    // `None` without a (complete) dynamic section.
+    fn dynamic(&self) -> Option<Dynamic> {
+        let headers = self.program_headers()?;
+        let dynamic = headers.iter().find(|it| it.ty == PT_DYNAMIC)?;
+        let word = if self.is_64 { 8 } else { 4 };
+        let mut entries = Vec::new();
+        for i in 0..dynamic.filesz / (2 * word) {
+            let entry = dynamic.offset.checked_add(i * 2 * word)?;
+            let tag = self.read_word(entry)?;
+            if tag == DT_NULL {
+                break;
+            }
+            entries.push((tag, self.read_word(entry.checked_add(word)?)?));
+        }
+        let strtab = entries.iter().find(|(tag, _)| *tag == DT_STRTAB)?.1;
+        // DT_STRTAB is a virtual address, mapped to the file by the PT_LOAD segment holding it.
+        // The header fields come from the file, so a crafted one can make this overflow.
+        let strtab = headers
+            .iter()
+            .filter(|it| it.ty == PT_LOAD)
+            .find(|it| {
+                it.vaddr
+                    .checked_add(it.filesz)
+                    .is_some_and(|end| (it.vaddr..end).contains(&strtab))
+            })
+            .and_then(|it| (strtab - it.vaddr).checked_add(it.offset))?;
+        let string = |offset: u64| -> Option<String> {
+            let start = usize::try_from(strtab.checked_add(offset)?).ok()?;
+            let bytes = self.contents.get(start..)?;
+            let len = bytes.iter().position(|it| *it == 0)?;
+            String::from_utf8(bytes[..len].to_vec()).ok()
+        };
+        let strings = |wanted: u64| {
+            entries
+                .iter()
+                .filter(move |(tag, _)| *tag == wanted)
+                .filter_map(move |(_, value)| string(*value))
+        };
+        Some(Dynamic {
+            needed: strings(DT_NEEDED).collect(),
+            rpath: strings(DT_RPATH).next(),
+            runpath: strings(DT_RUNPATH).next(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use std::fs;
+    use tempfile::TempDir;
+
+    // A 64-bit little-endian ELF header followed by one program header of each given type.
+    fn elf64(types: &[u32]) -> Vec<u8> {
+        let mut contents = vec![0; 64];
+        contents[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        contents[0x20..0x28].copy_from_slice(&64u64.to_le_bytes());
+        contents[0x36..0x38].copy_from_slice(&56u16.to_le_bytes());
+        contents[0x38..0x3a].copy_from_slice(&(types.len() as u16).to_le_bytes());
+        for ty in types {
+            let mut header = vec![0; 56];
+            header[..4].copy_from_slice(&ty.to_le_bytes());
+            contents.extend(header);
+        }
+        contents
+    }
+
+    // A 64-bit little-endian ELF file whose dynamic section needs `needed` with `runpath`.
+    fn elf64_dynamic(needed: &[&str], runpath: &str) -> Vec<u8> {
+        const BASE: u64 = 0x40_0000;
+        let mut strtab = vec![0];
+        let mut string = |value: &str| {
+            let offset = strtab.len() as u64;
+            strtab.extend(value.bytes().chain([0]));
+            offset
+        };
+        let mut dynamic: Vec<_> = needed.iter().map(|it| (DT_NEEDED, string(it))).collect();
+        dynamic.push((DT_RUNPATH, string(runpath)));
+        // The dynamic section follows the ELF header and the two program headers.
+        let dynamic_offset = 64 + 2 * 56;
+        let strtab_offset = dynamic_offset + (dynamic.len() as u64 + 2) * 16;
+        dynamic.push((DT_STRTAB, BASE + strtab_offset));
+        dynamic.push((DT_NULL, 0));
+        let len = strtab_offset + strtab.len() as u64;
+
+        let mut contents = elf64(&[]);
+        contents[0x38..0x3a].copy_from_slice(&2u16.to_le_bytes());
+        for (ty, offset, vaddr, filesz) in [
+            (PT_LOAD, 0, BASE, len),
+            (
+                PT_DYNAMIC,
+                dynamic_offset,
+                BASE + dynamic_offset,
+                strtab_offset - dynamic_offset,
+            ),
+        ] {
+            let mut header = vec![0; 56];
+            header[..4].copy_from_slice(&ty.to_le_bytes());
+            header[8..16].copy_from_slice(&offset.to_le_bytes());
+            header[16..24].copy_from_slice(&vaddr.to_le_bytes());
+            header[32..40].copy_from_slice(&filesz.to_le_bytes());
+            contents.extend(header);
+        }
+        for (tag, value) in dynamic {
+            contents.extend(tag.to_le_bytes());
+            contents.extend(value.to_le_bytes());
+        }
+        contents.extend(strtab);
+        contents
+    }
+
+    #[test]
+    fn test_find_with_deps() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::create_dir(dir.join("bin")).unwrap();
+        fs::create_dir(dir.join("lib")).unwrap();
+        fs::create_dir(dir.join("env-lib")).unwrap();
+        fs::write(dir.join("lib/libfake.so.1"), "").unwrap();
+        fs::write(dir.join("env-lib/libfake-env.so.2"), "").unwrap();
+        let needed = ["libfake.so.1", "libfake-env.so.2", "libfake-missing.so.3"];
+        fs::write(
+            dir.join("bin/fake-dynamic"),
+            elf64_dynamic(&needed, "$ORIGIN/../lib"),
+        )
+        .unwrap();
+        fs::write(dir.join("bin/fake-script"), "#!/bin/sh\n").unwrap();
+
+        let env_lib = dir.join("env-lib");
+        let bin = dir.join("bin");
+        let vars = [
+            ("PATH", bin.as_str()),
+            ("LD_LIBRARY_PATH", env_lib.as_str()),
+        ];
+        with_scoped_env(&vars, || {
+            let (path, deps) = find_with_deps("fake-dynamic").unwrap();
+            assert_eq!(path, dir.join("bin/fake-dynamic"));
+            assert_eq!(
+                deps,
+                [
+                    Utf8PathBuf::from(format!("{bin}/../lib/libfake.so.1")),
+                    env_lib.join("libfake-env.so.2"),
+                    "libfake-missing.so.3".into(),
+                ]
+            );
+            assert_eq!(
+                find_with_deps("fake-script"),
+                Some((dir.join("bin/fake-script"), Vec::new()))
+            );
+            assert_eq!(find_with_deps("non-existent-binary"), None);
+        });
+    }
+
+    #[test]
+    fn test_dynamic_overflow() {
+        let contents = elf64_dynamic(&["libfake.so.1"], "");
+        assert_eq!(
+            Elf::parse(&contents).unwrap().dynamic().unwrap().needed,
+            ["libfake.so.1"]
+        );
+        // The PT_LOAD `p_vaddr` and `p_offset`, then the PT_DYNAMIC `p_offset`.
+        for field in [64 + 16, 64 + 8, 64 + 56 + 8] {
+            let mut contents = contents.clone();
+            contents[field..field + 8].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+            assert!(Elf::parse(&contents).unwrap().dynamic().is_none());
+        }
+    }
+
+    #[test]
+    fn test_is_static() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::write(dir.join("static"), elf64(&[1, 1])).unwrap();
+        fs::write(dir.join("dynamic"), elf64(&[6, PT_INTERP, 1])).unwrap();
+        fs::write(dir.join("truncated"), &elf64(&[1])[..66]).unwrap();
+        fs::write(dir.join("script"), "#!/bin/sh\n").unwrap();
+
+        assert!(is_static(&dir.join("static")));
+        assert!(!is_static(&dir.join("dynamic")));
+        assert!(!is_static(&dir.join("truncated")));
+        assert!(!is_static(&dir.join("script")));
+        assert!(!is_static(&dir.join("missing")));
+    }
+
+    #[test]
+    fn test_find_static() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dynamic = Utf8PathBuf::try_from(temp_dir.path().join("dynamic")).unwrap();
+        let static_ = Utf8PathBuf::try_from(temp_dir.path().join("static")).unwrap();
+        fs::create_dir(&dynamic).unwrap();
+        fs::create_dir(&static_).unwrap();
+        fs::write(dynamic.join("fake-static"), elf64(&[PT_INTERP])).unwrap();
+        fs::write(static_.join("fake-static"), elf64(&[1])).unwrap();
+        fs::write(dynamic.join("fake-dynamic"), elf64(&[PT_INTERP])).unwrap();
+
+        let paths = std::env::join_paths([&dynamic, &static_]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(
+                find_static("fake-static"),
+                Some(static_.join("fake-static"))
+            );
+            assert_eq!(
+                find_static("fake-dynamic"),
+                Some(dynamic.join("fake-dynamic"))
+            );
+            assert_eq!(find_static("non-existent-binary"), None);
+        });
+    }
+}
@@ -0,0 +1,183 @@
+//! Searching with the variables of a dotenv-style file layered over the inherited environment.
+
+use std::{env, fs};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{env_key_eq, find_with_vars};
+
+/// find_with_env_file return the executable [`find`](crate::find) would resolve with the
+/// variables of the dotenv-style `env_file` (e.g. a project's `.env`) set, without modifying the
+/// process environment.
+///
+/// Each line of the file is `KEY=VALUE`, optionally prefixed with `export `; blank lines and lines
+/// starting with `#` are ignored. A value in single quotes is taken literally. Otherwise `$NAME`
+/// and `${NAME}` are expanded from the variables earlier in the file, then from the inherited
+/// environment (to nothing when unset), and `\` escapes the next character inside double quotes;
+/// unquoted values end at a ` #` comment. When a key is listed twice, the last value wins.
+///
+/// A PATH set in the file replaces the inherited one, so extend it with `PATH=./bin:$PATH`;
+/// relative entries are resolved against the current directory, like those of the inherited
+/// PATH. Variables the file doesn't set, including PATH and the one named after `exec`, are
+/// inherited. Returns `None` if the file can't be read.
+pub fn find_with_env_file(env_file: &Utf8Path, exec: &str) -> Option<Utf8PathBuf> {
+    let vars = parse_env_file(&fs::read_to_string(env_file).ok()?);
+    find_with_vars(exec, |key| match lookup(&vars, key) {
+        Some(value) => Some(value.into()),
+        None => env::var_os(key),
+    })
+}
+
+fn lookup<'a>(vars: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    vars.iter()
+        .rev()
+        .find(|(name, _)| env_key_eq(name.as_ref(), key))
+        .map(|(_, value)| value.as_str())
+}
+
+fn parse_env_file(contents: &str) -> Vec<(String, String)> {
+    let mut vars = Vec::new();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let value = if let Some(rest) = value.strip_prefix('\'') {
+            rest.split_once('\'')
+                .map_or(rest, |(literal, _)| literal)
+                .to_owned()
+        } else if let Some(rest) = value.strip_prefix('"') {
+            expand(rest, true, &vars)
+        } else {
+            let end = value.find(" #").unwrap_or(value.len());
+            expand(value[..end].trim_end(), false, &vars)
+        };
+        vars.push((key.trim().to_owned(), value));
+    }
+    vars
+}
+
+// Expands `$NAME` and `${NAME}` in `value`. A quoted value stops at the closing `"` and handles
+// `\` escapes.
+fn expand(value: &str, quoted: bool, vars: &[(String, String)]) -> String {
+    let mut expanded = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => break,
+            '\\' if quoted => match chars.next() {
+                Some('n') => expanded.push('\n'),
+                Some('t') => expanded.push('\t'),
+                Some(escaped) => expanded.push(escaped),
+                None => {}
+            },
+            '$' => {
+                let braced = chars.next_if_eq(&'{').is_some();
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                if name.is_empty() || (braced && chars.next_if_eq(&'}').is_none()) {
+                    // Not a reference, keep it as written.
+                    expanded.push('$');
+                    if braced {
+                        expanded.push('{');
+                    }
+                    expanded.push_str(&name);
+                    continue;
+                }
+                match lookup(vars, &name) {
+                    Some(value) => expanded.push_str(value),
+                    None => {
+                        let value = env::var_os(&name).unwrap_or_default();
+                        expanded.push_str(&value.to_string_lossy());
+                    }
+                }
+            }
+            c => expanded.push(c),
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_env_file() {
+        let _guard = lock_env();
+        let contents = r#"
+# A comment
+export TOOLS=/opt/tools
+SINGLE='$TOOLS # literal'
+DOUBLE="${TOOLS}/bin \"quoted\""
+UNQUOTED=$TOOLS/sbin # trailing comment
+INHERITED=$FAKE_ENV_FILE_INHERITED:$FAKE_ENV_FILE_UNSET
+DOLLAR=$ and ${unclosed ${}
+TOOLS=/opt/other
+"#;
+        with_scoped_env(&[("FAKE_ENV_FILE_INHERITED", "inherited")], || {
+            let vars = parse_env_file(contents);
+            let get = |key| lookup(&vars, key);
+            assert_eq!(get("TOOLS"), Some("/opt/other"));
+            assert_eq!(get("SINGLE"), Some("$TOOLS # literal"));
+            assert_eq!(get("DOUBLE"), Some("/opt/tools/bin \"quoted\""));
+            assert_eq!(get("UNQUOTED"), Some("/opt/tools/sbin"));
+            assert_eq!(get("INHERITED"), Some("inherited:"));
+            assert_eq!(get("DOLLAR"), Some("$ and ${unclosed ${}"));
+        });
+    }
+
+    #[test]
+    fn test_find_with_env_file() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let [project_bin, inherited_bin] = [dir.join("project-bin"), dir.join("inherited-bin")];
+        let name = format!("fake-env-file-tool{}", env::consts::EXE_SUFFIX);
+        for bin in [&project_bin, &inherited_bin] {
+            fs::create_dir(bin).unwrap();
+            fs::write(bin.join(&name), "").unwrap();
+        }
+        fs::write(
+            inherited_bin.join(format!("fake-inherited{}", env::consts::EXE_SUFFIX)),
+            "",
+        )
+        .unwrap();
+        let separator = if cfg!(windows) { ';' } else { ':' };
+        let env_file = dir.join(".env");
+        fs::write(
+            &env_file,
+            format!("PATH={project_bin}{separator}$PATH\nFAKE-OVERRIDE=/opt/override\n"),
+        )
+        .unwrap();
+
+        with_scoped_env(&[("PATH", inherited_bin.as_str())], || {
+            assert_eq!(
+                find_with_env_file(&env_file, "fake-env-file-tool"),
+                Some(project_bin.join(&name))
+            );
+            assert_eq!(
+                find_with_env_file(&env_file, "fake-inherited"),
+                Some(inherited_bin.join(format!("fake-inherited{}", env::consts::EXE_SUFFIX)))
+            );
+            assert_eq!(
+                find_with_env_file(&env_file, "fake-override"),
+                Some("/opt/override".into())
+            );
+            assert_eq!(env::var("PATH").unwrap(), inherited_bin.as_str());
+            assert_eq!(
+                find_with_env_file(&dir.join("missing"), "fake-env-file-tool"),
+                None
+            );
+        });
+    }
+}
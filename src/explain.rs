@@ -0,0 +1,273 @@
+//! Searching while recording why each candidate was rejected.
+
+use std::{env, fmt, path::PathBuf};
+
+use camino::Utf8PathBuf;
+
+use crate::{
+    binary_candidates, env_path, env_var_names, get_cargo_home, is_executable, normalize_entry,
+    trim_exec, Source,
+};
+
+/// Why a candidate consulted by [`find_explained`] did not produce a match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RejectReason {
+    /// Nothing exists at the candidate path.
+    Missing,
+    /// Something exists at the candidate path, but it is not a file.
+    NotAFile,
+    /// The variable is unset; the entry path holds the variable name.
+    Unset,
+    /// The PATH entry or variable value is not valid UTF-8; the entry path is lossily converted.
+    NotUtf8,
+    /// The candidate is a file the current user can't execute (on Unix, no execute bit is set).
+    PermissionDenied,
+    /// The PATH entry is a file rather than a directory, e.g. a misconfigured shell profile; the
+    /// entry path holds the PATH entry.
+    NotADirectory,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectReason::Missing => write!(f, "missing"),
+            RejectReason::NotAFile => write!(f, "not a file"),
+            RejectReason::Unset => write!(f, "unset"),
+            RejectReason::NotUtf8 => write!(f, "not UTF-8"),
+            RejectReason::NotADirectory => write!(f, "not a directory"),
+            RejectReason::PermissionDenied => write!(f, "permission denied"),
+        }
+    }
+}
+
+/// The error returned by [`find_explained`], listing every candidate consulted in search order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotFoundReport {
+    pub entries: Vec<(Source, Utf8PathBuf, RejectReason)>,
+}
+
+impl fmt::Display for NotFoundReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "checked ")?;
+        for (i, (source, path, reason)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match (source, reason) {
+                (Source::Env | Source::CargoHome, RejectReason::Unset) => {
+                    write!(f, "${path} unset")?
+                }
+                (Source::Path, RejectReason::NotADirectory) => {
+                    write!(f, "PATH entry {path} is a file, not a directory")?
+                }
+                _ => write!(f, "{path} ({reason})")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NotFoundReport {}
+
+/// find_explained return a `Utf8PathBuf` for the given executable like
+/// [`find_with_cargo_home`](crate::find_with_cargo_home), or a report of every PATH candidate,
+/// environment variable and CARGO_HOME candidate consulted and why each didn't match.
+///
+/// Unlike [`find_with_cargo_home`](crate::find_with_cargo_home), a PATH or CARGO_HOME file the
+/// current user can't execute is not a match but reported as [`RejectReason::PermissionDenied`],
+/// so the report can suggest fixing its permissions.
+///
+/// Collecting the report does slightly more work than a plain search: every PATH entry is stat'ed
+/// to report one that is a file as [`RejectReason::NotADirectory`] instead of probing into it,
+/// and every candidate that is not a file is stat'ed again to tell a missing path from, say, a
+/// directory.
+pub fn find_explained(exec: &str) -> Result<Utf8PathBuf, NotFoundReport> {
+    let exec = trim_exec(exec);
+    let mut entries = Vec::new();
+
+    let paths = env::var_os("PATH").unwrap_or_default();
+    for dir in env::split_paths(&paths) {
+        match Utf8PathBuf::try_from(dir) {
+            Ok(dir) if dir.is_file() => {
+                entries.push((Source::Path, dir, RejectReason::NotADirectory))
+            }
+            Ok(dir) => {
+                let candidate = normalize_entry(dir).join(exec);
+                if let Some(path) = probe_explained(Source::Path, candidate, &mut entries) {
+                    return Ok(path);
+                }
+            }
+            Err(err) => entries.push((
+                Source::Path,
+                lossy(err.into_path_buf()),
+                RejectReason::NotUtf8,
+            )),
+        }
+    }
+
+    for name in env_var_names(exec) {
+        let value = env::var_os(&name);
+        // Like `find_in_env`, the first variable that is set decides, even when it isn't UTF-8.
+        let is_set = value.is_some();
+        match value.map(env_path) {
+            Some(Ok(path)) => return Ok(path),
+            Some(Err(err)) => entries.push((
+                Source::Env,
+                lossy(err.into_path_buf()),
+                RejectReason::NotUtf8,
+            )),
+            None => entries.push((Source::Env, name.into(), RejectReason::Unset)),
+        }
+        if is_set {
+            break;
+        }
+    }
+
+    match get_cargo_home() {
+        Some(cargo_home) => {
+            let candidate = cargo_home.join("bin").join(exec);
+            if let Some(path) = probe_explained(Source::CargoHome, candidate, &mut entries) {
+                return Ok(path);
+            }
+        }
+        None => entries.push((Source::CargoHome, "CARGO_HOME".into(), RejectReason::Unset)),
+    }
+
+    Err(NotFoundReport { entries })
+}
+
+fn probe_explained(
+    source: Source,
+    path: Utf8PathBuf,
+    entries: &mut Vec<(Source, Utf8PathBuf, RejectReason)>,
+) -> Option<Utf8PathBuf> {
+    for candidate in binary_candidates(path) {
+        if is_executable(&candidate) {
+            return Some(candidate);
+        }
+        let reason = if candidate.is_file() {
+            RejectReason::PermissionDenied
+        } else if candidate.exists() {
+            RejectReason::NotAFile
+        } else {
+            RejectReason::Missing
+        };
+        entries.push((source, candidate, reason));
+    }
+    None
+}
+
+fn lossy(path: PathBuf) -> Utf8PathBuf {
+    path.to_string_lossy().into_owned().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_explained() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::create_dir(dir.join("fake-dir-binary")).unwrap();
+        fs::write(dir.join("fake-binary"), "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::write(dir.join("fake-denied-binary"), "").unwrap();
+            let executable = fs::Permissions::from_mode(0o755);
+            fs::set_permissions(dir.join("fake-binary"), executable).unwrap();
+        }
+
+        let paths = std::env::join_paths([dir.join("fake-binary"), dir.clone()]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(find_explained("fake-binary"), Ok(dir.join("fake-binary")));
+            let report = find_explained("non-existent-binary").unwrap_err();
+            assert_eq!(
+                report.entries[0],
+                (
+                    Source::Path,
+                    dir.join("fake-binary"),
+                    RejectReason::NotADirectory
+                )
+            );
+            assert!(report.to_string().starts_with(&format!(
+                "checked PATH entry {} is a file, not a directory, ",
+                dir.join("fake-binary")
+            )));
+        });
+
+        with_scoped_env(&[("PATH", dir.as_str())], || {
+            let report = find_explained("fake-dir-binary").unwrap_err();
+            assert_eq!(
+                report.entries[0],
+                (
+                    Source::Path,
+                    dir.join("fake-dir-binary"),
+                    RejectReason::NotAFile
+                )
+            );
+            assert!(report.entries.contains(&(
+                Source::Env,
+                "FAKE-DIR-BINARY".into(),
+                RejectReason::Unset
+            )));
+            assert!(report.to_string().contains("$FAKE-DIR-BINARY unset"));
+
+            #[cfg(unix)]
+            assert_eq!(
+                find_explained("fake-denied-binary").unwrap_err().entries[0],
+                (
+                    Source::Path,
+                    dir.join("fake-denied-binary"),
+                    RejectReason::PermissionDenied
+                )
+            );
+
+            let report = find_explained("non-existent-binary").unwrap_err();
+            assert_eq!(
+                report.entries[0],
+                (
+                    Source::Path,
+                    dir.join("non-existent-binary"),
+                    RejectReason::Missing
+                )
+            );
+            assert!(report.to_string().starts_with(&format!(
+                "checked {} (missing)",
+                dir.join("non-existent-binary")
+            )));
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_explained_non_utf8_env() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let _guard = lock_env();
+        let vars = [
+            ("PATH", ""),
+            ("FAKE-NON-UTF8", ""),
+            ("fake-non-utf8", "/path/to/lowercase"),
+        ];
+        with_scoped_env(&vars, || {
+            // Restored by the scope above.
+            std::env::set_var("FAKE-NON-UTF8", OsStr::from_bytes(b"/path/\xff"));
+            assert_eq!(crate::find_in_env("fake-non-utf8"), None);
+            let report = find_explained("fake-non-utf8").unwrap_err();
+            let env_entries: Vec<_> = report
+                .entries
+                .iter()
+                .filter(|(source, _, _)| *source == Source::Env)
+                .collect();
+            assert_eq!(env_entries.len(), 1);
+            assert_eq!(env_entries[0].2, RejectReason::NotUtf8);
+        });
+    }
+}
@@ -0,0 +1,1029 @@
+//! A configurable variant of [`find`](crate::find).
+
+use std::{
+    collections::HashMap,
+    env, fmt, fs, io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+
+use crate::{
+    binary_candidates, env_path, find_in_env, find_self, listing::tolerate, path_dirs, trim_exec,
+};
+
+/// How [`Finder`] derives the environment variable name from the executable name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum EnvCase {
+    /// The uppercased name, e.g. `CARGO` for `cargo`, with the Unix fallbacks of
+    /// [`find_in_env`](crate::find_in_env).
+    #[default]
+    Upper,
+    /// The name exactly as given.
+    AsIs,
+    /// The name as given, then the uppercased name.
+    Both,
+}
+
+/// How [`Finder::find_all`] orders its matches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TieBreak {
+    /// Search order: directories added with [`Finder::dir`], then PATH order.
+    #[default]
+    PathOrder,
+    /// Search order, but matches of equal priority, i.e. resolving to the same file like
+    /// `/bin/ls` and `/usr/bin/ls` on a merged-`/usr` system, are sorted by path among
+    /// themselves at the position of the first one, so their order doesn't depend on which
+    /// spelling comes first in PATH.
+    Lexicographic,
+}
+
+/// Finder searches PATH and then the environment variable named after the executable, like
+/// [`find`](crate::find), with options to tune how the search is done.
+///
+/// Directories added with [`Finder::dir`] are searched, in order, before PATH.
+///
+/// With the `serde` feature, a Finder serializes to its search plan for bug reports: the sources
+/// in search order, every configuration option, and `search_dirs`, the directories that would be
+/// searched. The latter reflects the live environment (PATH) at serialization time.
+///
+/// ```
+/// use executable_path_finder::Finder;
+///
+/// let cargo = Finder::new().follow_dir_symlinks(false).find("cargo");
+/// println!("{:?}", cargo);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Finder {
+    dirs: Vec<Utf8PathBuf>,
+    root: Option<Utf8PathBuf>,
+    fs_root: Option<Utf8PathBuf>,
+    allowed_dirs: Option<Vec<Utf8PathBuf>>,
+    follow_dir_symlinks: bool,
+    case_insensitive: bool,
+    prefer_no_extension: bool,
+    prefer_self_dir: bool,
+    env_case: EnvCase,
+    min_size: u64,
+    skip_noexec: bool,
+    tie_break: TieBreak,
+    require_under: Option<Utf8PathBuf>,
+    prober: Option<Prober>,
+    cache_ttl: Duration,
+    cache: ProbeCache,
+}
+
+impl Default for Finder {
+    fn default() -> Self {
+        Finder {
+            dirs: Vec::new(),
+            root: None,
+            fs_root: None,
+            allowed_dirs: None,
+            follow_dir_symlinks: true,
+            case_insensitive: false,
+            prefer_no_extension: false,
+            prefer_self_dir: false,
+            env_case: EnvCase::Upper,
+            min_size: 0,
+            skip_noexec: false,
+            tie_break: TieBreak::PathOrder,
+            require_under: None,
+            prober: None,
+            cache_ttl: Duration::ZERO,
+            cache: ProbeCache::default(),
+        }
+    }
+}
+
+impl Finder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a directory to search before PATH.
+    ///
+    /// A relative directory is resolved against the root set with [`Finder::with_root`], if any.
+    pub fn dir(mut self, dir: impl Into<Utf8PathBuf>) -> Self {
+        self.dirs.push(dir.into());
+        self
+    }
+
+    /// Resolve the relative directories added with [`Finder::dir`] against `root`.
+    ///
+    /// This lets a search plan be described with paths relative to an installation root known
+    /// only at runtime, e.g. an extracted bundle. Absolute directories are left as-is, and so are
+    /// PATH entries.
+    pub fn with_root(mut self, root: impl Into<Utf8PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Probe every absolute search directory under `root`, e.g. to resolve executables as they
+    /// would appear inside a target filesystem mounted at `/mnt/target`.
+    ///
+    /// With a root of `/mnt/target`, `/usr/bin/cargo` is checked at `/mnt/target/usr/bin/cargo`,
+    /// and that path is what gets returned. This applies to PATH entries and to the directories
+    /// added with [`Finder::dir`] once resolved against [`Finder::with_root`]; directories that
+    /// are still relative are searched as-is. [`Finder::allow_only_dirs`] compares the PATH entries
+    /// before they are prefixed. The path from the environment variable is returned unchanged.
+    pub fn with_fs_root(mut self, root: impl Into<Utf8PathBuf>) -> Self {
+        self.fs_root = Some(root.into());
+        self
+    }
+
+    /// Only search the PATH entries listed in `dirs`, skipping every other inherited PATH entry.
+    ///
+    /// Entries are compared by path components, so `/usr/bin/` matches `/usr/bin`. An empty
+    /// allowlist means nothing is searched from PATH; directories added with [`Finder::dir`] are
+    /// not filtered.
+    pub fn allow_only_dirs(mut self, dirs: &[Utf8PathBuf]) -> Self {
+        self.allowed_dirs = Some(dirs.to_vec());
+        self
+    }
+
+    /// Whether PATH entries that are themselves symlinks to directories are searched.
+    ///
+    /// Defaults to `true`, so a `~/bin` symlinked to another directory behaves like that
+    /// directory. Hardened setups can pass `false` to skip such entries; only the entry itself is
+    /// checked, not its parent components.
+    pub fn follow_dir_symlinks(mut self, follow: bool) -> Self {
+        self.follow_dir_symlinks = follow;
+        self
+    }
+
+    /// Match the executable name case-insensitively, e.g. find `Cargo` for `cargo` on Linux.
+    ///
+    /// Defaults to `false`. When enabled, every searched directory without an exact match is
+    /// listed to compare names, which costs a directory read per entry on the hot path. A
+    /// directory that can't be listed holds no match for [`Finder::find`]; see
+    /// [`Finder::try_find`] to detect it.
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Always try the bare executable name before its extension variants, e.g. the Linux `tool`
+    /// over `tool.exe` on WSL.
+    ///
+    /// Defaults to `false`, in which case the platform order of
+    /// [`probe_for_binary`](crate::probe_for_binary) applies: on Windows the `PATHEXT` extensions
+    /// come before the bare name. When enabled, the bare name comes first and the extension
+    /// variants follow in their usual (`PATHEXT`) order.
+    pub fn prefer_no_extension(mut self, prefer: bool) -> Self {
+        self.prefer_no_extension = prefer;
+        self
+    }
+
+    /// Search the directory of the running executable before every other source, e.g. so a bundled
+    /// tool always runs the companion shipped with it rather than a PATH copy of another version.
+    ///
+    /// Defaults to `false`. When enabled, the directory comes first, ahead of the directories
+    /// added with [`Finder::dir`], PATH and the environment variable, and it is neither rerooted
+    /// by [`Finder::with_fs_root`] nor filtered by [`Finder::allow_only_dirs`]. It is the parent of
+    /// [`find_self`](crate::find_self), i.e. symlinks to the running executable are resolved; if
+    /// that path can't be determined, the option is ignored and the other sources are searched as
+    /// usual.
+    pub fn prefer_self_dir(mut self, prefer: bool) -> Self {
+        self.prefer_self_dir = prefer;
+        self
+    }
+
+    /// How the environment variable consulted after PATH is named, see [`EnvCase`].
+    ///
+    /// Defaults to [`EnvCase::Upper`], matching [`find`](crate::find); tools whose override
+    /// variable is lowercase or mixed-case can use [`EnvCase::AsIs`] or [`EnvCase::Both`].
+    pub fn env_case(mut self, env_case: EnvCase) -> Self {
+        self.env_case = env_case;
+        self
+    }
+
+    /// Skip matches smaller than `bytes`, e.g. a corrupted zero-byte `cargo` left on PATH.
+    ///
+    /// Defaults to zero, so any file matches. This is a heuristic: a runnable binary is never
+    /// empty, but a tiny shell script can be, so the threshold should stay well below the size of
+    /// any legitimate match. The size comes from the same metadata call that checks for a file.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Skip matches on a filesystem mounted `noexec`, which can't be run even with the execute bit
+    /// set, e.g. `/tmp` on hardened Linux systems.
+    ///
+    /// Defaults to `false`. When enabled, every match is canonicalized and looked up in
+    /// `/proc/mounts`, which is read again for each match: the mount point that is the longest
+    /// prefix of the path decides. Without `/proc/mounts`, i.e. on platforms other than Linux,
+    /// nothing is skipped.
+    pub fn skip_noexec(mut self, skip: bool) -> Self {
+        self.skip_noexec = skip;
+        self
+    }
+
+    /// How [`Finder::find_all`] orders its matches, see [`TieBreak`].
+    ///
+    /// Defaults to [`TieBreak::PathOrder`], which preserves the search order exactly.
+    /// [`TieBreak::Lexicographic`] only reorders matches of equal priority, which makes the order
+    /// reproducible across machines, e.g. for golden tests, while distinct files keep their PATH
+    /// precedence. [`Finder::find`] always returns the first match in search order.
+    pub fn tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// Only accept matches inside `dir`, continuing the search past any other match, e.g. to trust
+    /// nothing but the user's own tools under [`home_dir`](crate::home::home_dir).
+    ///
+    /// The check compares canonicalized paths, so a symlink inside `dir` pointing outside of it is
+    /// rejected, as is a path from the environment variable that doesn't exist. If `dir` itself
+    /// can't be canonicalized, nothing matches.
+    pub fn require_under(mut self, dir: impl Into<Utf8PathBuf>) -> Self {
+        self.require_under = Some(dir.into());
+        self
+    }
+
+    /// Decide with `prober` whether a candidate path exists, for filesystems whose semantics (e.g.
+    /// unusual case folding) the default check doesn't fit.
+    ///
+    /// `prober` is called for each candidate in the usual order, see
+    /// [`probe_for_binary`](crate::probe_for_binary), and a candidate is a match when it returns
+    /// metadata. It replaces the default check entirely, including that the path is a file: no
+    /// other filesystem call is made to accept a candidate. The filters of [`Finder::min_size`]
+    /// (applied to the returned metadata), [`Finder::skip_noexec`] and [`Finder::require_under`]
+    /// still apply on top.
+    pub fn with_prober(
+        mut self,
+        prober: impl Fn(&Utf8Path) -> Option<fs::Metadata> + Send + Sync + 'static,
+    ) -> Self {
+        self.prober = Some(Prober(Arc::new(prober)));
+        self
+    }
+
+    /// Remember for `ttl` whether the executable resolved in a given directory, so repeated
+    /// lookups skip re-stat'ing files.
+    ///
+    /// A binary installed, moved or removed within the staleness window may go unnoticed until its
+    /// entry expires. A TTL of zero, the default, disables caching. A clone of the Finder starts
+    /// with an empty cache.
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// find return a `Utf8PathBuf` for the given executable using this configuration.
+    pub fn find(&self, exec: &str) -> Option<Utf8PathBuf> {
+        self.search(exec, false).unwrap_or_default()
+    }
+
+    /// try_find return the same as [`Finder::find`], but error out on the first searched
+    /// directory that exists and can't be listed for a [`Finder::case_insensitive`] lookup, like
+    /// [`list_all_strict`](crate::list_all_strict). Without case-insensitive matching, no
+    /// directory is listed and this never fails.
+    pub fn try_find(&self, exec: &str) -> io::Result<Option<Utf8PathBuf>> {
+        self.search(exec, true)
+    }
+
+    fn search(&self, exec: &str, strict: bool) -> io::Result<Option<Utf8PathBuf>> {
+        let exec = trim_exec(exec);
+        for dir in self.search_dirs() {
+            if let Some(found) = self.probe_dir(&dir, exec, strict)? {
+                return Ok(Some(found));
+            }
+        }
+        Ok(self
+            .find_in_env(exec)
+            .filter(|it| self.is_under_required(it)))
+    }
+
+    /// find_all return every match for `exec` in the searched directories, ordered as configured
+    /// with [`Finder::tie_break`].
+    ///
+    /// Each directory contributes at most one match, and a directory searched twice contributes
+    /// its match once. The environment variable is not consulted.
+    pub fn find_all(&self, exec: &str) -> Vec<Utf8PathBuf> {
+        let exec = trim_exec(exec);
+        let mut matches = Vec::new();
+        for path in self
+            .search_dirs()
+            .into_iter()
+            .filter_map(|dir| self.probe_dir(&dir, exec, false).unwrap_or_default())
+        {
+            if !matches.contains(&path) {
+                matches.push(path);
+            }
+        }
+        if self.tie_break == TieBreak::Lexicographic {
+            matches = sort_ties(matches);
+        }
+        matches
+    }
+
+    fn find_in_env(&self, exec: &str) -> Option<Utf8PathBuf> {
+        let names = match self.env_case {
+            EnvCase::Upper => return find_in_env(exec),
+            EnvCase::AsIs => vec![exec.to_owned()],
+            EnvCase::Both => vec![exec.to_owned(), exec.to_ascii_uppercase()],
+        };
+        names
+            .into_iter()
+            .find_map(env::var_os)
+            .and_then(|it| env_path(it).ok())
+    }
+
+    // Errors, only returned when `strict`, aren't cached.
+    fn probe_dir(
+        &self,
+        dir: &Utf8Path,
+        exec: &str,
+        strict: bool,
+    ) -> io::Result<Option<Utf8PathBuf>> {
+        if self.cache_ttl.is_zero() {
+            return self.probe_dir_uncached(dir, exec, strict);
+        }
+        let key = (dir.to_path_buf(), exec.to_owned());
+        if let Some((at, found)) = self.cache.lock().get(&key) {
+            if at.elapsed() < self.cache_ttl {
+                return Ok(found.clone());
+            }
+        }
+        let found = self.probe_dir_uncached(dir, exec, strict)?;
+        self.cache
+            .lock()
+            .insert(key, (Instant::now(), found.clone()));
+        Ok(found)
+    }
+
+    fn probe_dir_uncached(
+        &self,
+        dir: &Utf8Path,
+        exec: &str,
+        strict: bool,
+    ) -> io::Result<Option<Utf8PathBuf>> {
+        let candidates = self.candidates(dir.join(exec));
+        if let Some(found) = candidates.iter().find(|it| self.accepts(it)) {
+            return Ok(Some(found.clone()));
+        }
+        if self.case_insensitive {
+            return probe_case_insensitive(&candidates, strict, |it| self.accepts(it));
+        }
+        Ok(None)
+    }
+
+    fn accepts(&self, path: &Utf8Path) -> bool {
+        let metadata = match &self.prober {
+            Some(Prober(prober)) => prober(path),
+            None => path.metadata().ok().filter(fs::Metadata::is_file),
+        };
+        metadata.is_some_and(|metadata| metadata.len() >= self.min_size)
+            && !(self.skip_noexec && is_on_noexec_mount(path))
+            && self.is_under_required(path)
+    }
+
+    fn is_under_required(&self, path: &Utf8Path) -> bool {
+        let Some(dir) = &self.require_under else {
+            return true;
+        };
+        match (path.canonicalize_utf8(), dir.canonicalize_utf8()) {
+            (Ok(path), Ok(dir)) => path.starts_with(dir),
+            _ => false,
+        }
+    }
+
+    fn candidates(&self, path: Utf8PathBuf) -> Vec<Utf8PathBuf> {
+        let mut candidates: Vec<_> = binary_candidates(path.clone()).collect();
+        if self.prefer_no_extension {
+            candidates.retain(|it| *it != path);
+            candidates.insert(0, path);
+        }
+        candidates
+    }
+
+    fn search_dirs(&self) -> Vec<Utf8PathBuf> {
+        let extra_dirs = self.dirs.iter().map(|dir| match &self.root {
+            Some(root) if dir.is_relative() => root.join(dir),
+            _ => dir.clone(),
+        });
+        let path_dirs = path_dirs()
+            .into_iter()
+            .filter(|dir| match &self.allowed_dirs {
+                Some(allowed) => allowed.contains(dir),
+                None => true,
+            })
+            .map(|dir| self.reroot(dir))
+            .filter(|dir| self.follow_dir_symlinks || !is_symlink(dir));
+        let self_dir = self
+            .prefer_self_dir
+            .then(find_self)
+            .flatten()
+            .and_then(|it| it.parent().map(Utf8Path::to_path_buf));
+        self_dir
+            .into_iter()
+            .chain(extra_dirs.map(|dir| self.reroot(dir)))
+            .chain(path_dirs)
+            .collect()
+    }
+
+    fn reroot(&self, dir: Utf8PathBuf) -> Utf8PathBuf {
+        match &self.fs_root {
+            Some(fs_root) if dir.is_absolute() => {
+                let mut rerooted = fs_root.clone();
+                rerooted.extend(
+                    dir.components().filter(|it| {
+                        !matches!(it, Utf8Component::Prefix(_) | Utf8Component::RootDir)
+                    }),
+                );
+                rerooted
+            }
+            _ => dir,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Finder {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use crate::Source;
+
+        #[derive(serde::Serialize)]
+        struct Plan<'a> {
+            sources: [Source; 2],
+            dirs: &'a [Utf8PathBuf],
+            root: &'a Option<Utf8PathBuf>,
+            fs_root: &'a Option<Utf8PathBuf>,
+            allowed_dirs: &'a Option<Vec<Utf8PathBuf>>,
+            follow_dir_symlinks: bool,
+            case_insensitive: bool,
+            prefer_no_extension: bool,
+            prefer_self_dir: bool,
+            env_case: EnvCase,
+            min_size: u64,
+            skip_noexec: bool,
+            tie_break: TieBreak,
+            require_under: &'a Option<Utf8PathBuf>,
+            custom_prober: bool,
+            cache_ttl: Duration,
+            search_dirs: Vec<Utf8PathBuf>,
+        }
+
+        Plan {
+            // Searched after the extra directories, in this order.
+            sources: [Source::Path, Source::Env],
+            dirs: &self.dirs,
+            root: &self.root,
+            fs_root: &self.fs_root,
+            allowed_dirs: &self.allowed_dirs,
+            follow_dir_symlinks: self.follow_dir_symlinks,
+            case_insensitive: self.case_insensitive,
+            prefer_no_extension: self.prefer_no_extension,
+            prefer_self_dir: self.prefer_self_dir,
+            env_case: self.env_case,
+            min_size: self.min_size,
+            skip_noexec: self.skip_noexec,
+            tie_break: self.tie_break,
+            require_under: &self.require_under,
+            custom_prober: self.prober.is_some(),
+            cache_ttl: self.cache_ttl,
+            search_dirs: self.search_dirs(),
+        }
+        .serialize(serializer)
+    }
+}
+
+type ProbeFn = dyn Fn(&Utf8Path) -> Option<fs::Metadata> + Send + Sync;
+
+// A `Finder::with_prober` function, shared by clones of the Finder.
+#[derive(Clone)]
+struct Prober(Arc<ProbeFn>);
+
+impl fmt::Debug for Prober {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Prober(..)")
+    }
+}
+
+type CacheEntries = HashMap<(Utf8PathBuf, String), (Instant, Option<Utf8PathBuf>)>;
+
+#[derive(Debug, Default)]
+struct ProbeCache(Mutex<CacheEntries>);
+
+impl ProbeCache {
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheEntries> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+// Cached results are only valid for the configuration that produced them.
+impl Clone for ProbeCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+// Gathers the matches resolving to the same file at the position of the first one, sorted by path.
+fn sort_ties(matches: Vec<Utf8PathBuf>) -> Vec<Utf8PathBuf> {
+    let mut keyed: Vec<_> = matches
+        .into_iter()
+        .map(|path| (path.canonicalize_utf8().ok(), path))
+        .collect();
+    let mut sorted = Vec::with_capacity(keyed.len());
+    while !keyed.is_empty() {
+        let key = keyed[0].0.clone();
+        let (mut ties, rest): (Vec<_>, Vec<_>) = keyed
+            .into_iter()
+            .enumerate()
+            .partition(|(i, (it, _))| *i == 0 || key.is_some() && *it == key);
+        ties.sort_by(|(_, (_, a)), (_, (_, b))| a.cmp(b));
+        sorted.extend(ties.into_iter().map(|(_, (_, path))| path));
+        keyed = rest.into_iter().map(|(_, it)| it).collect();
+    }
+    sorted
+}
+
+fn probe_case_insensitive(
+    candidates: &[Utf8PathBuf],
+    strict: bool,
+    accepts: impl Fn(&Utf8Path) -> bool,
+) -> io::Result<Option<Utf8PathBuf>> {
+    let Some(parent) = candidates.first().and_then(|it| it.parent()) else {
+        return Ok(None);
+    };
+    let entries = parent.read_dir_utf8().map(|entries| {
+        entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.into_path())
+            .collect::<Vec<_>>()
+    });
+    let entries = tolerate(parent, entries, strict)?;
+    Ok(candidates.iter().find_map(|candidate| {
+        let name = candidate.file_name()?.to_lowercase();
+        entries
+            .iter()
+            .find(|entry| {
+                entry.file_name().map(str::to_lowercase).as_ref() == Some(&name) && accepts(entry)
+            })
+            .cloned()
+    }))
+}
+
+fn is_on_noexec_mount(path: &Utf8Path) -> bool {
+    let Ok(mounts) = fs::read_to_string("/proc/mounts") else {
+        return false;
+    };
+    let path = path
+        .canonicalize_utf8()
+        .unwrap_or_else(|_| path.to_path_buf());
+    is_noexec(&mounts, &path)
+}
+
+// Whether the mount of `path` in `mounts`, in the `/proc/mounts` format, has the `noexec` option.
+// When a mount point is mounted over, the later entry wins.
+fn is_noexec(mounts: &str, path: &Utf8Path) -> bool {
+    let mut best: Option<(Utf8PathBuf, bool)> = None;
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(mount_point), Some(options)) = (fields.nth(1), fields.nth(1)) else {
+            continue;
+        };
+        let mount_point = Utf8PathBuf::from(unescape_mount_field(mount_point));
+        let longer = best
+            .as_ref()
+            .is_none_or(|(it, _)| mount_point.as_str().len() >= it.as_str().len());
+        if longer && path.starts_with(&mount_point) {
+            best = Some((mount_point, options.split(',').any(|it| it == "noexec")));
+        }
+    }
+    best.is_some_and(|(_, noexec)| noexec)
+}
+
+// `/proc/mounts` escapes spaces and other special characters as octal, e.g. `\040`.
+fn unescape_mount_field(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(i) = rest.find('\\') {
+        unescaped.push_str(&rest[..i]);
+        let code = rest
+            .get(i + 1..i + 4)
+            .and_then(|it| u8::from_str_radix(it, 8).ok());
+        match code {
+            Some(code) => {
+                unescaped.push(char::from(code));
+                rest = &rest[i + 4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+fn is_symlink(dir: &Utf8Path) -> bool {
+    dir.symlink_metadata()
+        .map(|metadata| metadata.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use std::env;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_with_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = Utf8Path::from_path(temp_dir.path()).unwrap();
+        fs::create_dir_all(root.join("tools/bin")).unwrap();
+        let fake_bin = root.join("tools/bin/fake-bundled-binary");
+        fs::write(&fake_bin, "").unwrap();
+
+        let finder = Finder::new().dir("tools/bin");
+        assert_eq!(finder.find("fake-bundled-binary"), None);
+
+        let finder = finder.with_root(root);
+        assert_eq!(finder.find("fake-bundled-binary"), Some(fake_bin));
+
+        let absolute = Finder::new()
+            .dir(root.join("tools/bin"))
+            .with_root("/non-existent-root");
+        assert!(absolute.find("fake-bundled-binary").is_some());
+    }
+
+    #[test]
+    fn test_with_fs_root() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let fs_root = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let name = format!("fake-rooted-binary{}", env::consts::EXE_SUFFIX);
+        fs::create_dir_all(fs_root.join("usr/bin")).unwrap();
+        fs::create_dir_all(fs_root.join("opt/tool/bin")).unwrap();
+        fs::write(fs_root.join("usr/bin").join(&name), "").unwrap();
+        fs::write(fs_root.join("opt/tool/bin").join(&name), "").unwrap();
+
+        let (usr_bin, opt) = if cfg!(windows) {
+            ("C:\\usr\\bin", "C:\\opt")
+        } else {
+            ("/usr/bin", "/opt")
+        };
+        with_scoped_env(&[("PATH", usr_bin)], || {
+            let finder = Finder::new().with_fs_root(&fs_root);
+            assert_eq!(
+                finder.find("fake-rooted-binary"),
+                Some(fs_root.join("usr").join("bin").join(&name))
+            );
+            let finder = finder.dir("tool/bin").with_root(opt);
+            assert_eq!(
+                finder.find("fake-rooted-binary"),
+                Some(fs_root.join("opt").join("tool").join("bin").join(&name))
+            );
+        });
+    }
+
+    #[test]
+    fn test_allow_only_dirs() {
+        let _guard = lock_env();
+        let allowed = TempDir::new().unwrap();
+        let other = TempDir::new().unwrap();
+        fs::write(allowed.path().join("fake-allowed-binary"), "").unwrap();
+        fs::write(other.path().join("fake-other-binary"), "").unwrap();
+        let allowed_dir = Utf8PathBuf::try_from(allowed.path().to_path_buf()).unwrap();
+
+        let paths = env::join_paths([allowed.path(), other.path()]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            let finder = Finder::new().allow_only_dirs(std::slice::from_ref(&allowed_dir));
+            assert_eq!(
+                finder.find("fake-allowed-binary"),
+                Some(allowed_dir.join("fake-allowed-binary"))
+            );
+            assert_eq!(finder.find("fake-other-binary"), None);
+
+            let finder = Finder::new().allow_only_dirs(&[]);
+            assert_eq!(finder.find("fake-allowed-binary"), None);
+        });
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let _guard = lock_env();
+        let finder = Finder::new()
+            .dir("tools/bin")
+            .with_root("/opt/bundle")
+            .case_insensitive(true);
+        with_scoped_env(&[("PATH", "/usr/bin")], || {
+            let plan = serde_json::to_value(&finder).unwrap();
+            assert_eq!(plan["sources"], serde_json::json!(["Path", "Env"]));
+            assert_eq!(plan["dirs"], serde_json::json!(["tools/bin"]));
+            assert_eq!(plan["case_insensitive"], serde_json::json!(true));
+            assert_eq!(
+                plan["search_dirs"],
+                serde_json::json!(["/opt/bundle/tools/bin", "/usr/bin"])
+            );
+        });
+    }
+
+    #[test]
+    fn test_with_cache_ttl() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let fake_bin = dir.join("fake-cached-binary");
+
+        fs::write(&fake_bin, "").unwrap();
+        let finder = Finder::new().dir(&dir);
+        assert_eq!(finder.find("fake-cached-binary"), Some(fake_bin.clone()));
+        fs::remove_file(&fake_bin).unwrap();
+        assert_eq!(finder.find("fake-cached-binary"), None);
+
+        // Far longer than the test can take, so the entry is still cached.
+        fs::write(&fake_bin, "").unwrap();
+        let finder = Finder::new()
+            .dir(&dir)
+            .with_cache_ttl(Duration::from_secs(3600));
+        assert_eq!(finder.find("fake-cached-binary"), Some(fake_bin.clone()));
+        fs::remove_file(&fake_bin).unwrap();
+        assert_eq!(finder.find("fake-cached-binary"), Some(fake_bin.clone()));
+        assert_eq!(finder.clone().find("fake-cached-binary"), None);
+
+        // Expired by the time of the second lookup.
+        fs::write(&fake_bin, "").unwrap();
+        let finder = Finder::new()
+            .dir(&dir)
+            .with_cache_ttl(Duration::from_nanos(1));
+        assert_eq!(finder.find("fake-cached-binary"), Some(fake_bin.clone()));
+        fs::remove_file(&fake_bin).unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(finder.find("fake-cached-binary"), None);
+    }
+
+    #[test]
+    fn test_prefer_no_extension() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::write(dir.join("fake-tool"), "").unwrap();
+        fs::write(dir.join("fake-tool.exe"), "").unwrap();
+
+        let finder = Finder::new().dir(&dir).prefer_no_extension(true);
+        assert_eq!(finder.find("fake-tool"), Some(dir.join("fake-tool")));
+
+        let expected = if cfg!(windows) {
+            dir.join("fake-tool.exe")
+        } else {
+            dir.join("fake-tool")
+        };
+        assert_eq!(Finder::new().dir(&dir).find("fake-tool"), Some(expected));
+    }
+
+    #[test]
+    fn test_prefer_self_dir() {
+        let _guard = lock_env();
+        let this = find_self().unwrap();
+        let name = this.file_stem().unwrap();
+        with_scoped_env(&[("PATH", "")], || {
+            assert_eq!(Finder::new().find(name), None);
+            let finder = Finder::new().prefer_self_dir(true);
+            assert_eq!(finder.find(name), Some(this.clone()));
+            assert_eq!(finder.find("non-existent-binary"), None);
+        });
+    }
+
+    #[test]
+    fn test_with_prober() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::write(dir.join("FAKE-PROBED"), "").unwrap();
+        fs::write(dir.join("fake-rejected"), "").unwrap();
+
+        // A filesystem that stores every name uppercased.
+        let finder = Finder::new().dir(&dir).with_prober(|path| {
+            let name = path.file_name()?.to_uppercase();
+            fs::metadata(path.with_file_name(name)).ok()
+        });
+        assert_eq!(finder.find("fake-probed"), Some(dir.join("fake-probed")));
+        // Elsewhere, the real filesystem may already be case-insensitive.
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(finder.find("fake-rejected"), None);
+            assert_eq!(Finder::new().dir(&dir).find("fake-probed"), None);
+        }
+
+        let finder = Finder::new().dir(&dir).with_prober(|_| None);
+        assert_eq!(finder.find("fake-rejected"), None);
+    }
+
+    #[test]
+    fn test_tie_break() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let a = Utf8PathBuf::try_from(temp_dir.path().join("a")).unwrap();
+        let b = Utf8PathBuf::try_from(temp_dir.path().join("b")).unwrap();
+        let name = format!("fake-tie-binary{}", env::consts::EXE_SUFFIX);
+        for dir in [&a, &b] {
+            fs::create_dir(dir).unwrap();
+            fs::write(dir.join(&name), "").unwrap();
+        }
+
+        let finder = Finder::new().dir(&b).dir(&a).dir(&b);
+        assert_eq!(
+            finder.find_all("fake-tie-binary"),
+            [b.join(&name), a.join(&name)]
+        );
+        // Distinct files keep their PATH precedence.
+        let finder = finder.tie_break(TieBreak::Lexicographic);
+        assert_eq!(
+            finder.find_all("fake-tie-binary"),
+            [b.join(&name), a.join(&name)]
+        );
+        assert_eq!(finder.find("fake-tie-binary"), Some(b.join(&name)));
+
+        // `linked` points to `b`, so its match ties with `b`'s and is sorted next to it.
+        #[cfg(unix)]
+        {
+            let linked = Utf8PathBuf::try_from(temp_dir.path().join("linked")).unwrap();
+            std::os::unix::fs::symlink(&b, &linked).unwrap();
+            let finder = Finder::new().dir(&b).dir(&a).dir(&linked);
+            assert_eq!(
+                finder.find_all("fake-tie-binary"),
+                [b.join(&name), a.join(&name), linked.join(&name)]
+            );
+            let finder = finder.tie_break(TieBreak::Lexicographic);
+            assert_eq!(
+                finder.find_all("fake-tie-binary"),
+                [b.join(&name), linked.join(&name), a.join(&name)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_noexec() {
+        let mounts = "\
+/dev/sda1 / ext4 rw,relatime 0 0
+tmpfs /tmp tmpfs rw,nosuid,nodev,noexec 0 0
+/dev/sdb1 /tmp/build ext4 rw 0 0
+tmpfs /mnt/with\\040space tmpfs rw,noexec 0 0
+tmpfs /opt tmpfs rw,noexec 0 0
+/dev/sdc1 /opt ext4 rw 0 0
+";
+        let noexec = |path: &str| is_noexec(mounts, Utf8Path::new(path));
+        assert!(!noexec("/usr/bin/cargo"));
+        assert!(noexec("/tmp/cargo"));
+        assert!(!noexec("/tmp/build/cargo"));
+        assert!(!noexec("/tmpfoo/cargo"));
+        assert!(noexec("/mnt/with space/cargo"));
+        assert!(!noexec("/opt/cargo"));
+    }
+
+    #[test]
+    fn test_min_size() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let empty_dir = Utf8PathBuf::try_from(temp_dir.path().join("empty")).unwrap();
+        let full_dir = Utf8PathBuf::try_from(temp_dir.path().join("full")).unwrap();
+        let name = format!("fake-sized-binary{}", env::consts::EXE_SUFFIX);
+        fs::create_dir(&empty_dir).unwrap();
+        fs::create_dir(&full_dir).unwrap();
+        fs::write(empty_dir.join(&name), "").unwrap();
+        fs::write(full_dir.join(&name), "#!/bin/sh\n").unwrap();
+
+        let finder = Finder::new().dir(&empty_dir).dir(&full_dir);
+        assert_eq!(
+            finder.find("fake-sized-binary"),
+            Some(empty_dir.join(&name))
+        );
+        assert_eq!(
+            finder.min_size(1).find("fake-sized-binary"),
+            Some(full_dir.join(&name))
+        );
+    }
+
+    #[test]
+    fn test_require_under() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let outside = Utf8PathBuf::try_from(temp_dir.path().join("outside")).unwrap();
+        let home = Utf8PathBuf::try_from(temp_dir.path().join("home")).unwrap();
+        let name = format!("fake-trusted-binary{}", env::consts::EXE_SUFFIX);
+        fs::create_dir_all(&outside).unwrap();
+        fs::create_dir_all(home.join("bin")).unwrap();
+        fs::write(outside.join(&name), "").unwrap();
+        fs::write(home.join("bin").join(&name), "").unwrap();
+
+        let finder = Finder::new().dir(&outside).dir(home.join("bin"));
+        assert_eq!(
+            finder.find("fake-trusted-binary"),
+            Some(outside.join(&name))
+        );
+        let finder = finder.require_under(&home);
+        assert_eq!(
+            finder.find("fake-trusted-binary"),
+            Some(home.join("bin").join(&name))
+        );
+        #[cfg(unix)]
+        {
+            // A symlink under the allowed directory escaping it is rejected.
+            fs::create_dir(home.join("escape")).unwrap();
+            std::os::unix::fs::symlink(outside.join(&name), home.join("escape").join(&name))
+                .unwrap();
+            let finder = Finder::new().dir(home.join("escape")).require_under(&home);
+            assert_eq!(finder.find("fake-trusted-binary"), None);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_env_case() {
+        let _guard = lock_env();
+        let vars = [
+            ("fakeEnvCase", "/path/to/as-is"),
+            ("FAKEENVCASE", "/path/to/upper"),
+        ];
+        with_scoped_env(&vars, || {
+            let find = |env_case| Finder::new().env_case(env_case).find("fakeEnvCase");
+            assert_eq!(find(EnvCase::Upper), Some("/path/to/upper".into()));
+            assert_eq!(find(EnvCase::AsIs), Some("/path/to/as-is".into()));
+            assert_eq!(find(EnvCase::Both), Some("/path/to/as-is".into()));
+        });
+        with_scoped_env(&[("FAKEENVCASE", "/path/to/upper")], || {
+            let find = |env_case| Finder::new().env_case(env_case).find("fakeEnvCase");
+            assert_eq!(find(EnvCase::AsIs), None);
+            assert_eq!(find(EnvCase::Both), Some("/path/to/upper".into()));
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_case_insensitive() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::write(dir.join("Fake-Case-Binary"), "").unwrap();
+
+        let finder = Finder::new().dir(&dir);
+        assert_eq!(finder.find("fake-case-binary"), None);
+
+        let finder = finder.case_insensitive(true);
+        assert_eq!(
+            finder.find("fake-case-binary"),
+            Some(dir.join("Fake-Case-Binary"))
+        );
+        assert_eq!(finder.find("non-existent-binary"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_try_find_case_insensitive() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let [locked, open] = [dir.join("locked"), dir.join("open")];
+        fs::create_dir_all(&locked).unwrap();
+        fs::create_dir_all(&open).unwrap();
+        fs::write(open.join("Fake-Case-Binary"), "").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // Root can list the directory anyway, so there is no read error to surface.
+        let readable = locked.read_dir_utf8().is_ok();
+
+        let finder = Finder::new().dir(&locked).dir(&open).case_insensitive(true);
+        let found = finder.find("fake-case-binary");
+        let tried = finder.try_find("fake-case-binary");
+        let exact = Finder::new().dir(&locked).try_find("fake-case-binary");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        if cfg!(target_os = "linux") {
+            assert_eq!(found, Some(open.join("Fake-Case-Binary")));
+        }
+        if !readable {
+            assert!(tried.unwrap_err().to_string().contains(locked.as_str()));
+        }
+        // Without case-insensitive matching, nothing is listed.
+        assert_eq!(exact.unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_dir_symlinks() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let real_dir = temp_dir.path().join("real");
+        let linked_dir = temp_dir.path().join("linked");
+        fs::create_dir(&real_dir).unwrap();
+        fs::write(real_dir.join("fake-binary"), "").unwrap();
+        std::os::unix::fs::symlink(&real_dir, &linked_dir).unwrap();
+
+        with_scoped_env(&[("PATH", linked_dir.to_str().unwrap())], || {
+            let expected_path = Utf8PathBuf::try_from(linked_dir.join("fake-binary")).unwrap();
+            assert_eq!(Finder::new().find("fake-binary"), Some(expected_path));
+            assert_eq!(
+                Finder::new().follow_dir_symlinks(false).find("fake-binary"),
+                None
+            );
+        });
+    }
+}
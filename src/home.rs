@@ -53,7 +53,7 @@ use std::path::{Path, PathBuf};
 /// # Examples
 ///
 /// ```
-/// match home::home_dir() {
+/// match executable_path_finder::home::home_dir() {
 ///     Some(path) if !path.as_os_str().is_empty() => println!("{}", path.display()),
 ///     _ => println!("Unable to get your home dir!"),
 /// }
@@ -98,7 +98,7 @@ fn home_dir_inner() -> Option<PathBuf> {
 /// # Examples
 ///
 /// ```
-/// match home::cargo_home() {
+/// match executable_path_finder::home::cargo_home() {
 ///     Ok(path) => println!("{}", path.display()),
 ///     Err(err) => eprintln!("Cannot get your cargo home dir: {:?}", err),
 /// }
@@ -135,7 +135,7 @@ pub fn cargo_home_with_cwd(cwd: &Path) -> io::Result<PathBuf> {
 /// # Examples
 ///
 /// ```
-/// match home::rustup_home() {
+/// match executable_path_finder::home::rustup_home() {
 ///     Ok(path) => println!("{}", path.display()),
 ///     Err(err) => eprintln!("Cannot get your rustup home dir: {:?}", err),
 /// }
@@ -74,7 +74,7 @@ pub fn cargo_home_with_cwd_env(env: &dyn Env, cwd: &Path) -> io::Result<PathBuf>
         }
         _ => home_dir_with_env(env)
             .map(|p| p.join(".cargo"))
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "could not find cargo home dir")),
+            .ok_or_else(|| io::Error::other("could not find cargo home dir")),
     }
 }
 
@@ -103,6 +103,6 @@ pub fn rustup_home_with_cwd_env(env: &dyn Env, cwd: &Path) -> io::Result<PathBuf
         }
         _ => home_dir_with_env(env)
             .map(|d| d.join(".rustup"))
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "could not find rustup home dir")),
+            .ok_or_else(|| io::Error::other("could not find rustup home dir")),
     }
 }
@@ -28,7 +28,7 @@ fn home_dir_crt() -> Option<PathBuf> {
         ) {
             S_OK => {
                 let path_slice = slice::from_raw_parts(path, wcslen(path));
-                let s = OsString::from_wide(&path_slice);
+                let s = OsString::from_wide(path_slice);
                 CoTaskMemFree(path.cast());
                 Some(PathBuf::from(s))
             }
@@ -55,7 +55,6 @@ extern "C" {
 mod tests {
     use super::home_dir_inner;
     use std::env;
-    use std::ops::Deref;
     use std::path::{Path, PathBuf};
 
     #[test]
@@ -70,9 +69,9 @@ mod tests {
         let home = Path::new(r"C:\Users\foo tar baz");
 
         env::set_var("HOME", home.as_os_str());
-        assert_ne!(home_dir_inner().as_ref().map(Deref::deref), Some(home));
+        assert_ne!(home_dir_inner().as_deref(), Some(home));
 
         env::set_var("USERPROFILE", home.as_os_str());
-        assert_eq!(home_dir_inner().as_ref().map(Deref::deref), Some(home));
+        assert_eq!(home_dir_inner().as_deref(), Some(home));
     }
 }
@@ -0,0 +1,125 @@
+//! Searching the standard Windows install locations that are usually not on PATH.
+
+use std::{ffi::OsString, os::windows::ffi::OsStringExt, path::PathBuf, ptr, slice};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use windows_sys::{
+    core::GUID,
+    Win32::{
+        Foundation::S_OK,
+        System::Com::CoTaskMemFree,
+        UI::Shell::{
+            FOLDERID_ProgramFiles, FOLDERID_ProgramFilesX86, FOLDERID_UserProgramFiles,
+            SHGetKnownFolderPath, KF_FLAG_DONT_VERIFY,
+        },
+    },
+};
+
+use crate::{probe_for_binary, trim_exec};
+
+// How many directory levels below a known folder are searched: `Programs\Tool\tool.exe` is at
+// depth 1, `Git\cmd\git.exe` at depth 2.
+const MAX_DEPTH: usize = 2;
+
+/// find_in_known_folders return the first match for `exec` in the folders Windows installers put
+/// programs in, for GUI-installed tools that didn't register themselves on PATH.
+///
+/// The folders are searched in this order: the per-user `%LOCALAPPDATA%\Programs`
+/// (`FOLDERID_UserProgramFiles`), then `%ProgramFiles%` and `%ProgramFiles(x86)%`. In each, the
+/// folder itself is probed first, then its subdirectories and their subdirectories (two levels
+/// deep), each level in sorted order. Every subdirectory is listed, so this is much slower than
+/// [`find`](crate::find). Probing uses the same extension logic as PATH entries, see
+/// [`probe_for_binary`].
+pub fn find_in_known_folders(exec: &str) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    [
+        FOLDERID_UserProgramFiles,
+        FOLDERID_ProgramFiles,
+        FOLDERID_ProgramFilesX86,
+    ]
+    .iter()
+    .filter_map(known_folder)
+    .find_map(|folder| find_shallow(&folder, exec))
+}
+
+fn find_shallow(folder: &Utf8Path, exec: &str) -> Option<Utf8PathBuf> {
+    let mut level = vec![folder.to_path_buf()];
+    for depth in 0..=MAX_DEPTH {
+        if let Some(found) = level
+            .iter()
+            .find_map(|dir| probe_for_binary(dir.join(exec)))
+        {
+            return Some(found);
+        }
+        if depth == MAX_DEPTH {
+            break;
+        }
+        level = level.iter().flat_map(|dir| subdirectories(dir)).collect();
+    }
+    None
+}
+
+fn subdirectories(dir: &Utf8Path) -> Vec<Utf8PathBuf> {
+    let Ok(entries) = dir.read_dir_utf8() else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|it| it.is_dir()))
+        .map(|entry| entry.into_path())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn known_folder(id: &GUID) -> Option<Utf8PathBuf> {
+    let mut path = ptr::null_mut();
+    // SAFETY: `id` is a valid GUID and `path` a valid out pointer; a null token means the current
+    // user.
+    let result =
+        unsafe { SHGetKnownFolderPath(id, KF_FLAG_DONT_VERIFY as u32, ptr::null_mut(), &mut path) };
+    let folder = (result == S_OK).then(|| {
+        // SAFETY: on `S_OK`, `path` points to a valid NUL-terminated string, only freed below.
+        let wide = unsafe { slice::from_raw_parts(path, wide_len(path)) };
+        PathBuf::from(OsString::from_wide(wide))
+    });
+    // SAFETY: `path` was allocated by `SHGetKnownFolderPath`, or is null on failure, which is a
+    // no-op for `CoTaskMemFree`; it isn't used afterwards.
+    unsafe { CoTaskMemFree(path.cast()) };
+    Utf8PathBuf::try_from(folder?).ok()
+}
+
+// The length of the NUL-terminated string at `wide`, in code units.
+//
+// SAFETY: `wide` must point to a valid NUL-terminated string.
+unsafe fn wide_len(wide: *const u16) -> usize {
+    let mut len = 0;
+    // Bounded by the longest Windows path, 32,767 code units.
+    while len <= i16::MAX as usize && unsafe { *wide.add(len) } != 0 {
+        len += 1;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_shallow() {
+        let temp_dir = TempDir::new().unwrap();
+        let folder = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::create_dir_all(folder.join("Git/cmd")).unwrap();
+        fs::create_dir_all(folder.join("Deep/a/b")).unwrap();
+        fs::write(folder.join("Git/cmd/fake-git.exe"), "").unwrap();
+        fs::write(folder.join("Deep/a/b/fake-deep.exe"), "").unwrap();
+
+        assert_eq!(
+            find_shallow(&folder, "fake-git"),
+            Some(folder.join("Git/cmd/fake-git.exe"))
+        );
+        assert_eq!(find_shallow(&folder, "fake-deep"), None);
+    }
+}
@@ -1,149 +1,2803 @@
 //from rust-analyzer/crates/toolchain/src/lib.rs
 
-use std::{env, iter, path::PathBuf};
+use std::{
+    collections::HashMap,
+    env,
+    ffi::{OsStr, OsString},
+    fmt, fs, io, iter,
+    path::PathBuf,
+    process::Command,
+    time::SystemTime,
+};
 
-use camino::Utf8PathBuf;
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 
+mod audit;
+mod cargo_config;
+#[cfg(target_os = "linux")]
+mod desktop_entry;
+#[cfg(all(unix, feature = "elf"))]
+mod elf;
+mod env_file;
+mod explain;
+mod finder;
 pub mod home;
+#[cfg(all(windows, feature = "windows"))]
+mod known_folders;
+mod listing;
+#[cfg(all(unix, feature = "login-shell"))]
+mod login_shell;
+#[cfg(feature = "test-util")]
+mod overrides;
+#[cfg(all(windows, feature = "windows"))]
+mod registry_path;
+mod rustup;
+mod script;
+mod strategy;
+#[cfg(feature = "tokio")]
+mod stream;
+#[cfg(feature = "watch")]
+mod watch;
+
+pub use audit::{audit, find_audited, AuditAttempt, AuditEntry, AuditLog, AuditOutcome};
+pub use cargo_config::find_from_cargo_config;
+#[cfg(target_os = "linux")]
+pub use desktop_entry::find_from_desktop_entry;
+#[cfg(all(unix, feature = "elf"))]
+pub use elf::{find_static, find_with_deps, is_static};
+pub use env_file::find_with_env_file;
+pub use explain::{find_explained, NotFoundReport, RejectReason};
+pub use finder::{EnvCase, Finder, TieBreak};
+#[cfg(all(windows, feature = "windows"))]
+pub use known_folders::find_in_known_folders;
+pub use listing::{
+    find_by_stem, find_by_stem_strict, find_glob, find_glob_strict, find_in_dir_glob,
+    find_in_dir_glob_strict, find_with_version_fallback, find_with_version_fallback_strict,
+    list_all, list_all_strict,
+};
+#[cfg(all(unix, feature = "login-shell"))]
+pub use login_shell::find_in_login_shell_path;
+#[cfg(feature = "test-util")]
+pub use overrides::{clear_overrides, override_find};
+#[cfg(all(windows, feature = "windows"))]
+pub use registry_path::find_in_registry_path;
+pub use rustup::{find_with_managed_flag, resolve_rustup_proxy};
+pub use script::{resolve_exec_chain, script_kind, ScriptKind};
+pub use strategy::{find_with_strategy, EnvInPath, EnvThenPath, PathThenEnv, SourceStrategy};
+#[cfg(feature = "tokio")]
+pub use stream::find_all_stream;
+#[cfg(feature = "watch")]
+pub use watch::WatchingFinder;
 
 /// find return a `PathBuf` for the given executable, it tries to find it in PATH and environment variables.
 ///
 /// The current implementation checks three places for an executable to use:
 /// 1) $PATH/`<executable_name>`
-///      example: for cargo, this tries all paths in $PATH with appended `cargo`, returning the
-///      first that exists
+///    example: for cargo, this tries all paths in $PATH with appended `cargo`, returning the
+///    first that exists
 /// 2) Appropriate environment variable (erroring if this is set but not a usable executable)
-///      example: for cargo, this checks $CARGO environment variable; for rustc, $RUSTC; etc
+///    example: for cargo, this checks $CARGO environment variable; for rustc, $RUSTC; etc
 pub fn find(exec: &str) -> Option<Utf8PathBuf> {
+    #[cfg(feature = "test-util")]
+    if let Some(found) = overrides::lookup(exec) {
+        return found;
+    }
     find_in_path(exec).or_else(|| find_in_env(exec))
 }
 
+/// find_string return the executable resolved by [`find`] as an owned `String`, e.g. to hand it
+/// across an FFI boundary.
+///
+/// This is just `find(exec).map(Into::into)`.
+pub fn find_string(exec: &str) -> Option<String> {
+    find(exec).map(Into::into)
+}
+
 /// find_with_cargo_home return a `PathBuf` for the given executable, it tries to find it in PATH, environment variables and CARGO_HOME.
 ///
 /// The current implementation checks three places for an executable to use:
 /// 1) $PATH/`<executable_name>`
-///      example: for cargo, this tries all paths in $PATH with appended `cargo`, returning the
-///      first that exists
+///    example: for cargo, this tries all paths in $PATH with appended `cargo`, returning the
+///    first that exists
 /// 2) Appropriate environment variable (erroring if this is set but not a usable executable)
-///      example: for cargo, this checks $CARGO environment variable; for rustc, $RUSTC; etc
+///    example: for cargo, this checks $CARGO environment variable; for rustc, $RUSTC; etc
 /// 3) `$CARGO_HOME/bin/<executable_name>`
-///      where $CARGO_HOME defaults to ~/.cargo (see <https://doc.rust-lang.org/cargo/guide/cargo-home.html>)
-///      example: for cargo, this tries $CARGO_HOME/bin/cargo, or ~/.cargo/bin/cargo if $CARGO_HOME is unset.
-///      It seems that this is a reasonable place to try for cargo, rustc, and rustup
+///    where $CARGO_HOME defaults to ~/.cargo (see <https://doc.rust-lang.org/cargo/guide/cargo-home.html>)
+///    example: for cargo, this tries $CARGO_HOME/bin/cargo, or ~/.cargo/bin/cargo if $CARGO_HOME is unset.
+///    It seems that this is a reasonable place to try for cargo, rustc, and rustup
 pub fn find_with_cargo_home(exec: &str) -> Option<Utf8PathBuf> {
+    #[cfg(feature = "test-util")]
+    if let Some(found) = overrides::lookup(exec) {
+        return found;
+    }
     find_in_path(exec)
         .or_else(|| find_in_env(exec))
         .or_else(|| find_in_cargo_home(exec))
 }
 
+/// find_dir return the directory containing the executable resolved by [`find`].
+///
+/// For a match from an environment variable, this is the parent of the path the variable
+/// points to. Returns `None` when no executable is found or the resolved path has no parent
+/// component (e.g. `$CARGO=cargo`).
+pub fn find_dir(exec: &str) -> Option<Utf8PathBuf> {
+    find(exec)?
+        .parent()
+        .filter(|dir| !dir.as_str().is_empty())
+        .map(Utf8Path::to_path_buf)
+}
+
+/// find_install_prefix return the install prefix of the executable resolved by [`find`], e.g.
+/// `/usr/local` for `/usr/local/bin/cargo`, to locate sibling data such as `share/man`.
+///
+/// This is a heuristic: only a parent directory named exactly `bin` is stripped. Otherwise the
+/// parent directory itself is returned, like [`find_dir`] does. A relative `bin` without a parent
+/// component (e.g. `$TOOL=bin/tool`) is returned as-is, as there is no prefix to strip it to.
+pub fn find_install_prefix(exec: &str) -> Option<Utf8PathBuf> {
+    let dir = find_dir(exec)?;
+    let prefix = dir.parent().filter(|it| !it.as_str().is_empty());
+    match (dir.file_name(), prefix) {
+        (Some("bin"), Some(prefix)) => Some(prefix.to_path_buf()),
+        _ => Some(dir),
+    }
+}
+
+/// store_path return the location of `path` in a content-addressed package store, e.g. the
+/// `/nix/store/...-ripgrep-14.1.0/bin/rg` behind `~/.nix-profile/bin/rg`, to report where a match
+/// comes from.
+///
+/// `path` is canonicalized, resolving symlinks, and the result is returned if it lives under a
+/// known store: `/nix/store`, `/gnu/store` (Guix), or pnpm's virtual store `node_modules/.pnpm`.
+/// Hardlinks can't be traced back this way, as every link to a file is equally canonical, so a
+/// hardlink outside the store (as pnpm creates from its global store) yields `None`, as do paths
+/// that can't be canonicalized or aren't in a store.
+pub fn store_path(path: &Utf8Path) -> Option<Utf8PathBuf> {
+    let canonical = path.canonicalize_utf8().ok()?;
+    let in_nix_like_store = ["/nix/store", "/gnu/store"]
+        .iter()
+        .any(|store| canonical.starts_with(store));
+    let in_pnpm_store = canonical
+        .components()
+        .zip(canonical.components().skip(1))
+        .any(|(dir, sub)| dir.as_str() == "node_modules" && sub.as_str() == ".pnpm");
+    (in_nix_like_store || in_pnpm_store).then_some(canonical)
+}
+
+/// find_prefer_dir return the executable from the `preferred` directory if it exists there,
+/// falling back to [`find_with_cargo_home`] (PATH, environment variables and CARGO_HOME) on a miss.
+///
+/// The preferred directory is probed with the same extension logic as PATH entries, see
+/// [`probe_for_binary`].
+pub fn find_prefer_dir(preferred: &Utf8Path, exec: &str) -> Option<Utf8PathBuf> {
+    probe_for_binary(preferred.join(trim_exec(exec))).or_else(|| find_with_cargo_home(exec))
+}
+
+/// find_or_else return the executable resolved by [`find`], or else the path produced by
+/// `fallback`, e.g. after prompting the user or downloading the tool.
+///
+/// `fallback` is only called when [`find`] returns `None`. Its path is validated like a PATH
+/// candidate, see [`probe_for_binary`], so a path to something that doesn't exist or isn't a file
+/// yields `None`.
+pub fn find_or_else(
+    exec: &str,
+    fallback: impl FnOnce() -> Option<Utf8PathBuf>,
+) -> Option<Utf8PathBuf> {
+    find(exec).or_else(|| fallback().and_then(probe_for_binary))
+}
+
+/// find_and_open return the executable [`find`] would pick together with an open read handle to
+/// it, for launchers that must run exactly the file they resolved.
+///
+/// Each candidate is opened first and then checked through the handle, so the handle pins the
+/// very file that matched: a later rename or replacement of the path doesn't affect it. On Unix,
+/// the handle can be executed via `/proc/self/fd/<fd>` or `fexecve`; other platforms only get a
+/// handle to verify (e.g. hash) the file's contents. Unlike [`find`], a path from the environment
+/// variable is only returned if it can be opened as a file.
+pub fn find_and_open(exec: &str) -> Option<(Utf8PathBuf, fs::File)> {
+    let exec = trim_exec(exec);
+    path_dirs()
+        .into_iter()
+        .flat_map(|dir| binary_candidates(dir.join(exec)))
+        .chain(find_in_env(exec))
+        .find_map(|path| {
+            let file = fs::File::open(&path).ok()?;
+            let is_file = file.metadata().ok()?.is_file();
+            is_file.then_some((path, file))
+        })
+}
+
+/// find_arch_variant return the arch-suffixed variant `<base>-<arch>` of an executable, e.g.
+/// `qemu-system-x86_64` for `qemu-system`, falling back to the bare `base`.
+///
+/// The arch is [`env::consts::ARCH`] of the current process, such as `x86_64` or `aarch64`. Both
+/// names are resolved with [`find`], the suffixed one first.
+pub fn find_arch_variant(base: &str) -> Option<Utf8PathBuf> {
+    let base = trim_exec(base);
+    find(&format!("{base}-{}", env::consts::ARCH)).or_else(|| find(base))
+}
+
+/// find_platform_variant return `base` with the extension of the current platform appended,
+/// resolved with [`find`], e.g. `mytool.bat` on Windows and `mytool.sh` elsewhere for a helper
+/// script named differently per platform.
+///
+/// `windows_ext` is used on Windows and `unix_ext` on every other platform. A leading `.` is
+/// optional, and an empty extension resolves `base` itself, so `("mytool", "", "exe")` finds
+/// `mytool` on Unix and `mytool.exe` on Windows.
+pub fn find_platform_variant(base: &str, unix_ext: &str, windows_ext: &str) -> Option<Utf8PathBuf> {
+    let ext = if cfg!(windows) { windows_ext } else { unix_ext };
+    find(append_extension(Utf8Path::new(trim_exec(base)), ext).as_str())
+}
+
+/// How to spawn a resolved executable: run `program` with `args` prepended to the caller's own
+/// arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub program: Utf8PathBuf,
+    pub args: Vec<String>,
+}
+
+/// find_invocation return the [`Invocation`] needed to run the executable resolved by [`find`].
+///
+/// Native executables are run directly with no prefix arguments. On Windows, `.cmd` and `.bat`
+/// scripts can't be spawned on their own, so the program is the command interpreter (`%ComSpec%`,
+/// falling back to `cmd` on PATH) and the prefix arguments are `["/C", "<path>"]`.
+pub fn find_invocation(exec: &str) -> Option<Invocation> {
+    let path = find(exec)?;
+    if cfg!(windows) && is_batch_script(&path) {
+        let program = env::var_os("ComSpec")
+            .and_then(|it| Utf8PathBuf::try_from(PathBuf::from(it)).ok())
+            .or_else(|| find("cmd"))
+            .unwrap_or_else(|| Utf8PathBuf::from("cmd.exe"));
+        return Some(Invocation {
+            program,
+            args: vec!["/C".to_owned(), path.into_string()],
+        });
+    }
+    Some(Invocation {
+        program: path,
+        args: Vec::new(),
+    })
+}
+
+fn is_batch_script(path: &Utf8Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("cmd") || ext.eq_ignore_ascii_case("bat"))
+}
+
+/// find_near return `exec` from the install tree of an already located `anchor` executable, e.g.
+/// a companion tool shipped next to `cargo`.
+///
+/// The anchor's directory (`.`) is probed first, then its sibling `../bin`. PATH and the
+/// environment are not consulted.
+pub fn find_near(anchor: &Utf8Path, exec: &str) -> Option<Utf8PathBuf> {
+    const NEAR_DIRS: [&str; 2] = [".", "../bin"];
+
+    probe_subdirs(anchor.parent()?, &NEAR_DIRS, exec)
+}
+
+/// find_near_any return `exec` from the install tree of the first of `anchors` that has it, e.g.
+/// `rust-analyzer-proc-macro-srv` next to whichever toolchain's `rustc` was located.
+///
+/// Each anchor's directory (`.`) is probed, then its siblings `../bin` and `../libexec`, before
+/// moving on to the next anchor, so the order of `anchors` decides between several hits. Unlike
+/// [`find_near`], `../libexec` is included, where toolchains keep helpers that aren't meant to be
+/// on PATH. PATH and the environment are not consulted.
+pub fn find_near_any(anchors: &[Utf8PathBuf], exec: &str) -> Option<Utf8PathBuf> {
+    const NEAR_DIRS: [&str; 3] = [".", "../bin", "../libexec"];
+
+    anchors
+        .iter()
+        .find_map(|anchor| probe_subdirs(anchor.parent()?, &NEAR_DIRS, exec))
+}
+
+/// find_in_install_tree return `exec` from the install tree of the running executable, e.g. a
+/// helper shipped in `<exe_dir>/../libexec` by a self-contained distribution.
+///
+/// Each of `subdirs` is resolved relative to the directory of [`find_self`], so a symlink to the
+/// running executable is followed to the real install tree, and probed in order like a PATH
+/// entry. Returns `None` if the running executable's path can't be determined.
+pub fn find_in_install_tree(exec: &str, subdirs: &[&str]) -> Option<Utf8PathBuf> {
+    probe_subdirs(find_self()?.parent()?, subdirs, exec)
+}
+
+fn probe_subdirs(dir: &Utf8Path, subdirs: &[&str], exec: &str) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    subdirs
+        .iter()
+        .find_map(|rel| probe_for_binary(normalize(&dir.join(rel)).join(exec)))
+}
+
+/// find_self return the path of the running executable.
+///
+/// The path from [`env::current_exe`] is canonicalized, so a symlink (or a `/proc/self/exe` style
+/// path) is resolved to the real file. Returns `None` if the path can't be determined,
+/// canonicalized, or isn't valid UTF-8.
+pub fn find_self() -> Option<Utf8PathBuf> {
+    let exe = env::current_exe().ok()?.canonicalize().ok()?;
+    Utf8PathBuf::try_from(exe).ok()
+}
+
+/// find_in_cargo_home return `$CARGO_HOME/bin/<exec>` if it exists, with the same extension logic
+/// as PATH entries (see [`probe_for_binary`]), so `.exe` binaries and `.cmd` shims installed by
+/// `cargo install` are found on Windows.
 pub fn find_in_cargo_home(exec: &str) -> Option<Utf8PathBuf> {
     let mut path = get_cargo_home()?;
     path.push("bin");
-    path.push(exec);
+    path.push(trim_exec(exec));
+    probe_for_binary(path)
+}
+
+/// find_in_virtualenv return `<exec>` from the scripts directory of the active Python virtualenv,
+/// i.e. `$VIRTUAL_ENV/bin` on Unix and `$VIRTUAL_ENV\Scripts` on Windows, with the same extension
+/// logic as PATH entries (see [`probe_for_binary`]).
+///
+/// Returns `None` when `VIRTUAL_ENV` is unset or not valid UTF-8. PATH is not consulted, so callers
+/// wanting the usual lookup as a fallback can chain [`find`].
+pub fn find_in_virtualenv(exec: &str) -> Option<Utf8PathBuf> {
+    let mut path = Utf8PathBuf::try_from(PathBuf::from(env::var_os("VIRTUAL_ENV")?)).ok()?;
+    path.push(if cfg!(windows) { "Scripts" } else { "bin" });
+    path.push(trim_exec(exec));
     probe_for_binary(path)
 }
 
+/// find_in_homebrew return `<prefix>/bin/<exec>` or, failing that, `<prefix>/sbin/<exec>` from the
+/// Homebrew prefix, as a fallback for [`find`] when the shell profile that puts Homebrew on PATH
+/// didn't run.
+///
+/// The prefix is `$HOMEBREW_PREFIX` when set and non-empty, otherwise the default for the
+/// platform: `/opt/homebrew` on Apple Silicon, `/usr/local` on Intel macOS and
+/// `/home/linuxbrew/.linuxbrew` elsewhere.
+pub fn find_in_homebrew(exec: &str) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    let prefix = env::var_os("HOMEBREW_PREFIX")
+        .filter(|it| !it.is_empty())
+        .and_then(|it| Utf8PathBuf::try_from(PathBuf::from(it)).ok())
+        .unwrap_or_else(|| {
+            if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+                "/opt/homebrew".into()
+            } else if cfg!(target_os = "macos") {
+                "/usr/local".into()
+            } else {
+                "/home/linuxbrew/.linuxbrew".into()
+            }
+        });
+    ["bin", "sbin"]
+        .iter()
+        .find_map(|dir| probe_for_binary(prefix.join(dir).join(exec)))
+}
+
+/// find_in_flatpak_exports return `exec` from the directories Flatpak exports app launchers to,
+/// which aren't always on PATH: the per-user `~/.local/share/flatpak/exports/bin`, then the
+/// system-wide `/var/lib/flatpak/exports/bin`.
+///
+/// Directories that don't exist, or a home directory that can't be determined, are skipped
+/// silently. The launchers are named after the app ID, e.g. `org.gimp.GIMP`.
+#[cfg(target_os = "linux")]
+pub fn find_in_flatpak_exports(exec: &str) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    let user_dir = home::home_dir()
+        .and_then(|it| Utf8PathBuf::try_from(it).ok())
+        .map(|home| home.join(".local/share/flatpak/exports/bin"));
+    user_dir
+        .into_iter()
+        .chain(iter::once("/var/lib/flatpak/exports/bin".into()))
+        .find_map(|dir| probe_for_binary(dir.join(exec)))
+}
+
+/// find_in_env return the path stored in the environment variable named after `exec`.
+///
+/// On Unix, variable names are case-sensitive, so the uppercased name (`CARGO` for `cargo`) is
+/// tried first, followed by the name as given and its lowercased form. On Windows, variable
+/// names are already case-insensitive and only the uppercased name is looked up.
+///
+/// A single pair of quotes surrounding the value, as left by some Windows launchers, is stripped.
 pub fn find_in_env(exec: &str) -> Option<Utf8PathBuf> {
-    env::var_os(exec.to_ascii_uppercase())
-        .map(PathBuf::from)
-        .map(Utf8PathBuf::try_from)
+    env_var_names(trim_exec(exec))
+        .into_iter()
+        .find_map(env::var_os)
+        .map(env_path)
         .and_then(Result::ok)
 }
 
+fn env_path(value: OsString) -> Result<Utf8PathBuf, camino::FromPathBufError> {
+    let path = Utf8PathBuf::try_from(PathBuf::from(value))?;
+    Ok(strip_quotes(path.as_str()).into())
+}
+
+fn strip_quotes(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|it| it.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+#[cfg(not(windows))]
+fn env_var_names(exec: &str) -> Vec<String> {
+    let mut names = vec![exec.to_ascii_uppercase()];
+    for name in [exec.to_owned(), exec.to_ascii_lowercase()] {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+#[cfg(windows)]
+fn env_var_names(exec: &str) -> Vec<String> {
+    vec![exec.to_ascii_uppercase()]
+}
+
 pub fn find_in_path(exec: &str) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
     let paths = env::var_os("PATH").unwrap_or_default();
+    if let Some(found) = find_in_short_path(&paths, exec) {
+        return found;
+    }
+    find_in_path_dirs(exec)
+}
+
+/// find_for_command return what `exec` resolves to in the environment `cmd` will be spawned with:
+/// PATH and the environment variable named after `exec` (see [`find`]) are taken from the
+/// command's overrides (`Command::env`, `Command::env_remove`), falling back to this process's
+/// environment for the ones it doesn't override.
+///
+/// Only what [`Command::get_envs`] exposes is honored. `Command::env_clear` can't be observed on
+/// stable Rust, so variables the command doesn't set explicitly are still inherited here. The
+/// extensions tried on Windows come from this process's `PATHEXT`, and relative PATH entries are
+/// resolved against this process's current directory rather than the command's.
+pub fn find_for_command(cmd: &Command, exec: &str) -> Option<Utf8PathBuf> {
+    find_with_vars(exec, |key| {
+        match cmd.get_envs().find(|(it, _)| env_key_eq(it, key)) {
+            Some((_, value)) => value.map(OsStr::to_owned),
+            None => env::var_os(key),
+        }
+    })
+}
+
+// Like `find`, with every variable read through `var`.
+fn find_with_vars(exec: &str, var: impl Fn(&str) -> Option<OsString>) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    let paths = var("PATH").unwrap_or_default();
+    path_dirs_in(&paths)
+        .into_iter()
+        .find_map(|dir| probe_for_binary(dir.join(exec)))
+        .or_else(|| {
+            env_var_names(exec)
+                .into_iter()
+                .find_map(|name| var(&name))
+                .and_then(|it| env_path(it).ok())
+        })
+}
+
+// Whether `name` names the variable `key`, ignoring case on Windows like the OS does.
+fn env_key_eq(name: &OsStr, key: &str) -> bool {
+    name.to_str().is_some_and(|it| {
+        if cfg!(windows) {
+            it.eq_ignore_ascii_case(key)
+        } else {
+            it == key
+        }
+    })
+}
+
+/// find_in_path_var return the first match for `exec` in the directories listed in the variable
+/// `var_name`, which is split like PATH, e.g. a custom `PLUGIN_PATH`.
+///
+/// Directories are probed in order like PATH entries, see [`probe_for_binary`], and entries that
+/// are not valid UTF-8 are skipped. Returns `None` when the variable is unset.
+pub fn find_in_path_var(var_name: &str, exec: &str) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    let paths = env::var_os(var_name)?;
     env::split_paths(&paths)
-        .map(|path| path.join(exec))
-        .map(PathBuf::from)
-        .map(Utf8PathBuf::try_from)
-        .filter_map(Result::ok)
+        .filter_map(|dir| Utf8PathBuf::try_from(dir).ok())
+        .find_map(|dir| probe_for_binary(normalize_entry(dir).join(exec)))
+}
+
+fn find_in_path_dirs(exec: &str) -> Option<Utf8PathBuf> {
+    path_dirs()
+        .into_iter()
+        .map(|dir| dir.join(exec))
         .find_map(probe_for_binary)
 }
 
-pub fn probe_for_binary(path: Utf8PathBuf) -> Option<Utf8PathBuf> {
-    let with_extension = match env::consts::EXE_EXTENSION {
-        "" => None,
-        it => Some(path.with_extension(it)),
+const SHORT_PATH_MAX_ENTRIES: usize = 8;
+
+// Fast path for the common container PATH of a few entries (`/usr/local/bin:/usr/bin:/bin`):
+// probes through one reused buffer instead of allocating a `Utf8PathBuf` per entry and candidate.
+// Returns `None` when it doesn't apply, i.e. on platforms with executable extensions, for a
+// non-UTF-8 PATH, for a longer PATH, or for an entry the general search would normalize (`.` or
+// `..` components, or repeated separators as in `/usr//bin`); the result is otherwise identical
+// to the general search.
+fn find_in_short_path(paths: &OsStr, exec: &str) -> Option<Option<Utf8PathBuf>> {
+    if cfg!(windows) || !env::consts::EXE_EXTENSION.is_empty() {
+        return None;
+    }
+    let paths = paths.to_str()?;
+    if paths.matches(':').count() >= SHORT_PATH_MAX_ENTRIES {
+        return None;
+    }
+    let needs_normalizing = paths.contains("//")
+        || paths
+            .split([':', '/'])
+            .any(|component| component == "." || component == "..");
+    if needs_normalizing {
+        return None;
+    }
+    let mut candidate = Utf8PathBuf::with_capacity(paths.len() + exec.len() + 1);
+    for dir in paths.split(':') {
+        candidate.clear();
+        candidate.push(dir);
+        candidate.push(exec);
+        if candidate.is_file() {
+            return Some(Some(candidate));
+        }
+    }
+    Some(None)
+}
+
+/// find_all return every match for `exec` on PATH, in PATH order, e.g. to see which copies the
+/// first one shadows.
+///
+/// Each PATH entry contributes at most one match, probed like in [`find_in_path`]; a directory
+/// listed twice in PATH contributes its match once. Environment variables are not consulted.
+pub fn find_all(exec: &str) -> Vec<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    let mut matches = Vec::new();
+    for path in path_dirs()
+        .into_iter()
+        .filter_map(|dir| probe_for_binary(dir.join(exec)))
+    {
+        if !matches.contains(&path) {
+            matches.push(path);
+        }
+    }
+    matches
+}
+
+/// find_distinct return the distinct files behind the matches of [`find_all`], in first-seen
+/// order, e.g. to count how many copies of a tool really exist when some PATH entries are
+/// symlinks to others.
+///
+/// Each match is canonicalized, resolving symlinks, and the canonical path is returned once. A
+/// match that can't be canonicalized is kept as-is and only collapses with an identical path.
+/// Hardlinks to the same file are separate paths and are all returned.
+pub fn find_distinct(exec: &str) -> Vec<Utf8PathBuf> {
+    let mut distinct = Vec::new();
+    for path in find_all(exec) {
+        let target = path.canonicalize_utf8().unwrap_or(path);
+        if !distinct.contains(&target) {
+            distinct.push(target);
+        }
+    }
+    distinct
+}
+
+/// find_grouped return the matches for `exec` on PATH grouped by the directory containing them,
+/// e.g. to display which copies are shadowed per directory.
+///
+/// Directories come in PATH order and only those with a match are listed, once each. Within a
+/// directory, every extension variant that exists is listed, in the order [`probe_for_binary`]
+/// tries them (see [`executable_extensions`]), so the first file of the first group is the match
+/// of [`find_in_path`]. Environment variables are not consulted.
+pub fn find_grouped(exec: &str) -> Vec<(Utf8PathBuf, Vec<Utf8PathBuf>)> {
+    let exec = trim_exec(exec);
+    path_dirs()
+        .into_iter()
+        .filter_map(|dir| {
+            let files: Vec<_> = binary_candidates(dir.join(exec))
+                .filter(|it| it.is_file())
+                .collect();
+            (!files.is_empty()).then_some((dir, files))
+        })
+        .collect()
+}
+
+/// find_counted return the executable resolved by [`find`] together with the number of probes the
+/// lookup performed, e.g. to measure lookup cost on a pathological PATH.
+///
+/// A probe is one existence check of a candidate path: every PATH entry costs one probe per
+/// extension variant tried (see [`executable_extensions`]) until the match is found. Reading the
+/// environment variable is not a probe.
+pub fn find_counted(exec: &str) -> (Option<Utf8PathBuf>, usize) {
+    let exec = trim_exec(exec);
+    let mut probes = 0;
+    let found = path_dirs()
+        .into_iter()
+        .flat_map(|dir| binary_candidates(dir.join(exec)))
+        .find(|it| {
+            probes += 1;
+            it.is_file()
+        });
+    (found.or_else(|| find_in_env(exec)), probes)
+}
+
+/// The candidates [`find`] would check for an executable, in order, as returned by [`plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SearchPlan {
+    /// Each candidate path with the source it comes from. [`Source::Path`] candidates are probed
+    /// for existence; the [`Source::Env`] one is returned as-is when reached.
+    pub candidates: Vec<(Source, Utf8PathBuf)>,
+}
+
+/// plan return the [`SearchPlan`] of [`find`] for `exec`: every PATH candidate with its extension
+/// variants expanded (see [`executable_extensions`]), followed by the value of the environment
+/// variable named after `exec`, if set, e.g. to document or troubleshoot a lookup.
+///
+/// Nothing is read from disk, so PATH entries that don't exist are still listed and the plan says
+/// nothing about which candidate matches. It reflects the environment (PATH, `PATHEXT` and the
+/// variable) at the time of the call.
+pub fn plan(exec: &str) -> SearchPlan {
+    let exec = trim_exec(exec);
+    let candidates = path_dirs()
+        .into_iter()
+        .flat_map(|dir| binary_candidates(dir.join(exec)))
+        .map(|path| (Source::Path, path))
+        .chain(find_in_env(exec).map(|path| (Source::Env, path)))
+        .collect();
+    SearchPlan { candidates }
+}
+
+/// find_mapped resolve each `(exec_name, override_env_var)` entry, checking the override variable
+/// first and then PATH, for config-driven tool maps.
+///
+/// Unlike [`find_in_env`], a path from the override variable is only used if it exists, as
+/// checked by [`probe_for_binary`]; otherwise PATH is searched for `exec_name`. Results are keyed
+/// on the exec name, so a later duplicate entry overwrites an earlier one.
+pub fn find_mapped(entries: &[(&str, &str)]) -> HashMap<String, Option<Utf8PathBuf>> {
+    entries
+        .iter()
+        .map(|(exec, var)| {
+            let found = env::var_os(var)
+                .and_then(|it| env_path(it).ok())
+                .and_then(probe_for_binary)
+                .or_else(|| find_in_path(exec));
+            (exec.to_string(), found)
+        })
+        .collect()
+}
+
+/// find_in_path_file return the first match for `exec` in the directories listed in `path_file`,
+/// for hermetic builds that describe PATH in a file rather than the environment.
+///
+/// The file lists one directory per line, searched in order; a line may also hold several
+/// directories joined with the platform PATH separator (`:` on Unix, `;` on Windows). Blank lines
+/// are skipped. Errors reading the file are returned as-is.
+pub fn find_in_path_file(path_file: &Utf8Path, exec: &str) -> io::Result<Option<Utf8PathBuf>> {
+    let exec = trim_exec(exec);
+    let contents = fs::read_to_string(path_file)?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .flat_map(env::split_paths)
+        .filter_map(|dir| Utf8PathBuf::try_from(dir).ok())
+        .find_map(|dir| probe_for_binary(dir.join(exec))))
+}
+
+/// find_in_dirs_lazy return the first match for `exec` in `dirs`, pulling directories from the
+/// iterator one at a time.
+///
+/// The search short-circuits: once a match is found, no further directory is requested from
+/// `dirs`, so directories that are expensive to produce (e.g. network mounts that must be woken)
+/// are only touched when every earlier one missed. Each directory is probed like a PATH entry, see
+/// [`probe_for_binary`]; PATH and the environment are not consulted.
+pub fn find_in_dirs_lazy(
+    exec: &str,
+    mut dirs: impl Iterator<Item = Utf8PathBuf>,
+) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    dirs.find_map(|dir| probe_for_binary(dir.join(exec)))
+}
+
+/// find_ranked_extensions return the first PATH match for `exec` with one of the extensions in
+/// `exts`, which are ranked by priority.
+///
+/// For each PATH directory, the extensions are tried in order (`""` stands for the bare name) and
+/// the first existing file is returned; the next directory is only searched when none of them
+/// exist. Extensions are appended, so `tool.sh` is tried for `"sh"` even if `exec` has a dot.
+/// They may be given with or without the leading dot: `".sh"` and `"sh"` are the same.
+pub fn find_ranked_extensions(exec: &str, exts: &[&str]) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    path_dirs().into_iter().find_map(|dir| {
+        let path = dir.join(exec);
+        exts.iter()
+            .map(|ext| append_extension(&path, ext))
+            .find(|it| it.is_file())
+    })
+}
+
+/// find_with_index return the PATH match for `exec` together with the zero-based index of the
+/// PATH entry it was found in, e.g. to explain that a match at position 2 shadows one at 7.
+///
+/// Only PATH is searched: a match from an environment variable or CARGO_HOME has no PATH index,
+/// so use [`find`] or [`find_with_cargo_home`] when `None` is returned. Entries that are not valid
+/// UTF-8 are skipped but still counted.
+pub fn find_with_index(exec: &str) -> Option<(Utf8PathBuf, usize)> {
+    let exec = trim_exec(exec);
+    let paths = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&paths)
+        .enumerate()
+        .filter_map(|(index, dir)| Some((Utf8PathBuf::try_from(dir).ok()?, index)))
+        .find_map(|(dir, index)| Some((probe_for_binary(normalize_entry(dir).join(exec))?, index)))
+}
+
+/// find_newer_than return the first PATH match for `exec` modified strictly after `reference`,
+/// e.g. to decide whether a build step has to be rerun.
+///
+/// A match modified exactly at `reference` is not newer and is skipped, as are matches whose
+/// modified time is unavailable. The search stops at the first qualifying match in PATH order.
+pub fn find_newer_than(exec: &str, reference: SystemTime) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    path_dirs()
+        .into_iter()
+        .filter_map(|dir| probe_metadata(dir.join(exec)))
+        .find(|(_, metadata)| metadata.modified().is_ok_and(|it| it > reference))
+        .map(|(path, _)| path)
+}
+
+/// find_checked_freshest return the first PATH match for `exec` together with whether it is the
+/// freshest of all PATH matches, e.g. to warn that a newly installed tool is shadowed by an old
+/// copy earlier in PATH.
+///
+/// The flag is `false` when a later PATH match has a modified time equal to or newer than the
+/// first one's, so a tie counts as shadowing. A later match that resolves to the same file as the
+/// first one, e.g. through a symlinked directory like `/bin` -> `/usr/bin`, doesn't shadow it.
+/// Matches whose modified time is unavailable are ignored, and the flag is `true` if the first
+/// match's own is. Without any match, this returns `(None, true)`. Every PATH entry is probed,
+/// like [`find_all`].
+pub fn find_checked_freshest(exec: &str) -> (Option<Utf8PathBuf>, bool) {
+    let exec = trim_exec(exec);
+    let mut matches = path_dirs()
+        .into_iter()
+        .filter_map(|dir| probe_metadata(dir.join(exec)));
+    let Some((first, metadata)) = matches.next() else {
+        return (None, true);
+    };
+    let canonical = first.canonicalize_utf8().ok();
+    let freshest = match metadata.modified() {
+        Ok(modified) => !matches.any(|(path, metadata)| {
+            let same_file =
+                path == first || canonical.is_some() && path.canonicalize_utf8().ok() == canonical;
+            !same_file && metadata.modified().is_ok_and(|it| it >= modified)
+        }),
+        Err(_) => true,
     };
-    iter::once(path)
-        .chain(with_extension)
-        .find(|it| it.is_file())
+    (Some(first), freshest)
 }
 
-fn get_cargo_home() -> Option<Utf8PathBuf> {
-    if let Some(path) = env::var_os("CARGO_HOME") {
-        return Utf8PathBuf::try_from(PathBuf::from(path)).ok();
+/// A PATH match handed to the ranking closure of [`find_best`].
+#[derive(Debug, Clone)]
+pub struct FoundBinary {
+    pub path: Utf8PathBuf,
+    pub metadata: fs::Metadata,
+}
+
+/// find_best return the PATH match for `exec` that `rank` scores highest, e.g. to prefer an install
+/// prefix and then the newest copy; ties go to the earlier match in PATH order.
+///
+/// Like [`find_all`], every PATH entry is probed and the matches' metadata is read before ranking,
+/// so this costs more than [`find`], which stops at the first match. Environment variables are not
+/// consulted.
+pub fn find_best(exec: &str, rank: impl Fn(&FoundBinary) -> i64) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    let mut best: Option<(i64, Utf8PathBuf)> = None;
+    for (path, metadata) in path_dirs()
+        .into_iter()
+        .filter_map(|dir| probe_metadata(dir.join(exec)))
+    {
+        let score = rank(&FoundBinary {
+            path: path.clone(),
+            metadata,
+        });
+        if best.as_ref().is_none_or(|(best, _)| score > *best) {
+            best = Some((score, path));
+        }
     }
+    best.map(|(_, path)| path)
+}
 
-    if let Some(mut path) = home::home_dir() {
-        path.push(".cargo");
-        return Utf8PathBuf::try_from(path).ok();
+/// The outcome of [`compare_sources`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceComparison {
+    /// PATH and the environment variable resolve to the same file, given as its canonical path.
+    Same(Utf8PathBuf),
+    /// PATH and the environment variable resolve to different files.
+    Different { path: Utf8PathBuf, env: Utf8PathBuf },
+    /// Only PATH has a match.
+    OnlyPath(Utf8PathBuf),
+    /// Only the environment variable is set.
+    OnlyEnv(Utf8PathBuf),
+    /// Neither source has a match.
+    Neither,
+}
+
+/// compare_sources resolve `exec` from PATH ([`find_in_path`]) and from its environment variable
+/// ([`find_in_env`]) independently and report whether they agree, e.g. to tell whether `$CARGO`
+/// shadows a different cargo than the one on PATH.
+///
+/// Both paths are compared after [`fs::canonicalize`], which makes them absolute and resolves
+/// symlinks; a path that can't be canonicalized, such as a missing file, is compared as-is.
+pub fn compare_sources(exec: &str) -> SourceComparison {
+    let canonical = |path: &Utf8Path| {
+        path.canonicalize_utf8()
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    match (find_in_path(exec), find_in_env(exec)) {
+        (Some(path), Some(env)) => {
+            let canonical_path = canonical(&path);
+            if canonical_path == canonical(&env) {
+                SourceComparison::Same(canonical_path)
+            } else {
+                SourceComparison::Different { path, env }
+            }
+        }
+        (Some(path), None) => SourceComparison::OnlyPath(path),
+        (None, Some(env)) => SourceComparison::OnlyEnv(env),
+        (None, None) => SourceComparison::Neither,
     }
+}
 
-    None
+/// try_find return the executable [`find`] would pick, or a [`FindError`] telling why none was
+/// found.
+///
+/// Unlike [`find`], a PATH match the current user can't execute (on Unix, a file without any
+/// execute bit) is skipped like the shell does; when nothing else matches, the first such file is
+/// reported as [`FindError::PermissionDenied`].
+///
+/// [`find`] also skips PATH entries and environment values that are not valid UTF-8. When nothing
+/// else matches, try_find checks whether one of those skipped locations does hold the executable
+/// and reports it as [`FindError::NonUtf8Match`] instead of a plain [`FindError::NotFound`].
+pub fn try_find(exec: &str) -> Result<Utf8PathBuf, FindError> {
+    let exec = trim_exec(exec);
+    let mut denied = None;
+    let mut non_utf8 = None;
+
+    let paths = env::var_os("PATH").unwrap_or_default();
+    for dir in env::split_paths(&paths) {
+        match Utf8PathBuf::try_from(dir) {
+            Ok(dir) => match probe_for_binary(normalize_entry(dir).join(exec)) {
+                Some(path) if is_executable(&path) => return Ok(path),
+                Some(path) if denied.is_none() => denied = Some(path),
+                _ => {}
+            },
+            Err(err) if non_utf8.is_none() => {
+                let dir = err.into_path_buf();
+                non_utf8 = binary_candidates(exec.into())
+                    .map(|it| dir.join(it))
+                    .find(|it| it.is_file());
+            }
+            Err(_) => {}
+        }
+    }
+
+    if let Some(value) = env_var_names(exec).into_iter().find_map(env::var_os) {
+        match env_path(value) {
+            Ok(path) => return Ok(path),
+            Err(err) => {
+                let path = err.into_path_buf();
+                if non_utf8.is_none() && path.is_file() {
+                    non_utf8 = Some(path);
+                }
+            }
+        }
+    }
+
+    Err(match (denied, non_utf8) {
+        (Some(path), _) => FindError::PermissionDenied { path },
+        (None, Some(path)) => FindError::NonUtf8Match {
+            lossy: path.to_string_lossy().into_owned(),
+        },
+        (None, None) => FindError::NotFound,
+    })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::env;
-    use std::fs;
-    use tempfile::TempDir;
+/// The error returned by [`try_find`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FindError {
+    /// No executable was found.
+    NotFound,
+    /// The executable exists, but only at a path that is not valid UTF-8, given lossily converted.
+    NonUtf8Match { lossy: String },
+    /// A file with the executable's name exists at `path`, but the current user can't execute it.
+    PermissionDenied { path: Utf8PathBuf },
+}
 
-    #[test]
-    fn test_find_in_path() {
-        let temp_dir = TempDir::new().unwrap();
-        let fake_bin = temp_dir.path().join("fake-binary");
-        fs::write(&fake_bin, "").unwrap();
+impl fmt::Display for FindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindError::NotFound => write!(f, "executable not found"),
+            FindError::NonUtf8Match { lossy } => {
+                write!(f, "executable found at a non-UTF-8 path: {lossy}")
+            }
+            FindError::PermissionDenied { path } => {
+                write!(f, "permission denied: {path} is not executable")
+            }
+        }
+    }
+}
 
-        let old_path = env::var_os("PATH");
-        env::set_var("PATH", temp_dir.path());
+impl std::error::Error for FindError {}
 
-        let expected_path = Utf8PathBuf::try_from(fake_bin).unwrap();
-        assert_eq!(find_in_path("fake-binary"), Some(expected_path));
-        assert_eq!(find_in_path("non-existent-binary"), None);
+/// find_unambiguous return the executable `find` would pick, erroring when PATH holds more than
+/// one distinct copy of it.
+///
+/// Every PATH entry is probed and the matches are compared by their canonicalized path, so
+/// symlinks (or repeated PATH entries) resolving to the same target are not considered
+/// ambiguous. When nothing matches on PATH, the environment variable is used like in [`find`].
+pub fn find_unambiguous(exec: &str) -> Result<Utf8PathBuf, AmbiguityError> {
+    let exec = trim_exec(exec);
+    let matches: Vec<Utf8PathBuf> = path_dirs()
+        .into_iter()
+        .filter_map(|dir| probe_for_binary(dir.join(exec)))
+        .collect();
 
-        // Restore the original PATH
-        if let Some(path) = old_path {
-            env::set_var("PATH", path);
-        } else {
-            env::remove_var("PATH");
+    let mut targets = Vec::new();
+    let mut distinct = Vec::new();
+    for path in &matches {
+        let target = path.canonicalize_utf8().unwrap_or_else(|_| path.clone());
+        if !targets.contains(&target) {
+            targets.push(target);
+            distinct.push(path.clone());
         }
     }
-    #[test]
-    fn test_find_in_env() {
-        env::set_var("TESTEXEC", "/path/to/testexec");
-        assert!(find_in_env("testexec").is_some());
-        assert!(find_in_env("nonexistent").is_none());
-        env::remove_var("TESTEXEC");
+
+    match distinct.len() {
+        0 => find_in_env(exec).ok_or(AmbiguityError::NotFound),
+        1 => Ok(matches.into_iter().next().unwrap()),
+        _ => Err(AmbiguityError::Ambiguous(distinct)),
     }
+}
 
-    #[test]
-    fn test_find_with_cargo_home() {
-        let temp_dir = TempDir::new().unwrap();
-        let fake_cargo_home = temp_dir.path().join(".cargo");
-        fs::create_dir_all(fake_cargo_home.join("bin")).unwrap();
-        let fake_bin = fake_cargo_home.join("bin").join("fake-cargo-binary");
-        fs::write(&fake_bin, "").unwrap();
+/// The error returned by [`find_unambiguous`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmbiguityError {
+    /// No executable was found.
+    NotFound,
+    /// Several distinct executables were found on PATH, listed in PATH order.
+    Ambiguous(Vec<Utf8PathBuf>),
+}
 
-        env::set_var("CARGO_HOME", fake_cargo_home);
+impl fmt::Display for AmbiguityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmbiguityError::NotFound => write!(f, "executable not found"),
+            AmbiguityError::Ambiguous(paths) => {
+                write!(f, "found multiple distinct executables on PATH:")?;
+                for path in paths {
+                    write!(f, " {path}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
 
-        assert!(find_with_cargo_home("fake-cargo-binary").is_some());
-        assert!(find_with_cargo_home("non-existent-binary").is_none());
+impl std::error::Error for AmbiguityError {}
 
-        env::remove_var("CARGO_HOME");
+// Config templating sometimes hands us `cargo/`; only the trailing separators are dropped so an
+// exec containing a relative path keeps its inner separators.
+fn trim_exec(exec: &str) -> &str {
+    exec.trim_end_matches(std::path::is_separator)
+}
+
+fn append_extension(path: &Utf8Path, ext: &str) -> Utf8PathBuf {
+    match ext.strip_prefix('.').unwrap_or(ext) {
+        "" => path.to_path_buf(),
+        ext => Utf8PathBuf::from(format!("{path}.{ext}")),
     }
+}
 
-    #[test]
-    fn test_probe_for_binary() {
-        let temp_dir = TempDir::new().unwrap();
-        let fake_bin = temp_dir.path().join("fake-binary");
-        fs::write(&fake_bin, "").unwrap();
+// The UTF-8 PATH entries in order. Absolute entries are normalized lexically, so
+// `/usr/local/bin/../bin` becomes `/usr/bin` without resolving symlinks, and repeated entries are
+// dropped after the first. Relative entries are kept as-is, as they depend on the current dir.
+fn path_dirs() -> Vec<Utf8PathBuf> {
+    path_dirs_in(&env::var_os("PATH").unwrap_or_default())
+}
 
-        assert!(probe_for_binary(Utf8PathBuf::try_from(fake_bin).unwrap()).is_some());
-        assert!(probe_for_binary(
-            Utf8PathBuf::try_from(temp_dir.path().join("non-existent")).unwrap()
-        )
-        .is_none());
+fn path_dirs_in(paths: &OsStr) -> Vec<Utf8PathBuf> {
+    let mut dirs = Vec::new();
+    for dir in env::split_paths(paths).filter_map(|dir| Utf8PathBuf::try_from(dir).ok()) {
+        let dir = normalize_entry(dir);
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+// A PATH entry as searched: absolute entries are normalized lexically, relative ones kept as-is.
+fn normalize_entry(dir: Utf8PathBuf) -> Utf8PathBuf {
+    if dir.is_absolute() {
+        normalize(&dir)
+    } else {
+        dir
+    }
+}
+
+/// is_on_path return whether `path` lives directly in one of the directories listed in PATH.
+///
+/// The parent directory of `path` and the PATH entries are compared as normalized absolute paths:
+/// relative paths are joined to the current directory and `.`/`..` components are collapsed
+/// lexically. Symlinks are not resolved.
+pub fn is_on_path(path: &Utf8Path) -> bool {
+    let Some(parent) = path.parent() else {
+        return false;
+    };
+    let parent = normalize(&absolute(parent));
+    path_dirs()
+        .iter()
+        .any(|dir| normalize(&absolute(dir)) == parent)
+}
+
+/// The PATH directories that differ between the current environment and another, as returned by
+/// [`path_diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PathDiff {
+    /// Directories on the current PATH that the other PATH lacks, in current PATH order.
+    pub only_in_current: Vec<Utf8PathBuf>,
+    /// Directories on the other PATH that the current PATH lacks, in the other PATH's order.
+    pub only_in_other: Vec<Utf8PathBuf>,
+}
+
+/// path_diff compare the directories of the current PATH with those of `other_path`, a PATH value
+/// from another context (e.g. the GUI session vs. a terminal), to see why a tool is found in one
+/// and not the other.
+///
+/// `other_path` is split like PATH on the current platform. Entries of both are compared as
+/// normalized absolute paths, like in [`is_on_path`]: relative entries are joined to the current
+/// directory, `.`/`..` components are collapsed lexically and trailing separators are ignored,
+/// while symlinks are not resolved and the comparison is case-sensitive. Entries that are not
+/// valid UTF-8 are skipped and each directory is listed once. Order alone is not a difference.
+pub fn path_diff(other_path: &str) -> PathDiff {
+    let dirs = |paths: &OsStr| -> Vec<_> {
+        let mut dirs = Vec::new();
+        for dir in path_dirs_in(paths) {
+            let dir = normalize(&absolute(&dir));
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        dirs
+    };
+    let current = dirs(&env::var_os("PATH").unwrap_or_default());
+    let other = dirs(OsStr::new(other_path));
+    let missing_from = |dirs: &[Utf8PathBuf], from: &[Utf8PathBuf]| -> Vec<_> {
+        dirs.iter()
+            .filter(|it| !from.contains(it))
+            .cloned()
+            .collect()
+    };
+    PathDiff {
+        only_in_current: missing_from(&current, &other),
+        only_in_other: missing_from(&other, &current),
+    }
+}
+
+/// find_relative_to return the executable resolved by [`find`] relative to `base` when it lives
+/// under `base`, e.g. to write a relocatable tool reference into a project config.
+///
+/// Both paths are compared as normalized absolute paths, like in [`is_on_path`]; symlinks are not
+/// resolved. A match outside `base` is returned as a normalized absolute path instead.
+pub fn find_relative_to(base: &Utf8Path, exec: &str) -> Option<Utf8PathBuf> {
+    let found = normalize(&absolute(&find(exec)?));
+    let base = normalize(&absolute(base));
+    match found.strip_prefix(&base) {
+        Ok(relative) => Some(relative.to_path_buf()),
+        Err(_) => Some(found),
+    }
+}
+
+fn absolute(path: &Utf8Path) -> Utf8PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    env::current_dir()
+        .ok()
+        .and_then(|cwd| Utf8PathBuf::try_from(cwd).ok())
+        .map(|cwd| cwd.join(path))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+// Collapses `.` and `..` components without touching the filesystem.
+fn normalize(path: &Utf8Path) -> Utf8PathBuf {
+    let mut normalized = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// probe_for_binary return the first existing file among `path` and its executable-extension
+/// variants.
+///
+/// On Windows, the `PATHEXT` extensions are tried in order before the bare `path`, matching the
+/// OS: `cargo.exe` wins over an extension-less `cargo` file. A `path` that already ends with one
+/// of these extensions is tried as-is.
+pub fn probe_for_binary(path: Utf8PathBuf) -> Option<Utf8PathBuf> {
+    binary_candidates(path).find(|it| it.is_file())
+}
+
+// Like `probe_for_binary`, also handing back the metadata fetched to check the match is a file.
+fn probe_metadata(path: Utf8PathBuf) -> Option<(Utf8PathBuf, fs::Metadata)> {
+    binary_candidates(path).find_map(|candidate| {
+        let metadata = candidate.metadata().ok().filter(fs::Metadata::is_file)?;
+        Some((candidate, metadata))
+    })
+}
+
+#[cfg(windows)]
+fn binary_candidates(path: Utf8PathBuf) -> impl Iterator<Item = Utf8PathBuf> {
+    let extensions = path_extensions();
+    let has_extension = path
+        .extension()
+        .is_some_and(|it| extensions.iter().any(|ext| ext.eq_ignore_ascii_case(it)));
+    let with_extensions: Vec<_> = if has_extension {
+        Vec::new()
+    } else {
+        extensions
+            .iter()
+            .map(|ext| append_extension(&path, ext))
+            .collect()
+    };
+    with_extensions.into_iter().chain(iter::once(path))
+}
+
+/// executable_extensions return the extensions [`probe_for_binary`] tries, in probe order, without
+/// the leading dot; `""` stands for the bare name.
+///
+/// On Windows, these are the lowercased `PATHEXT` extensions, read from the live environment on
+/// every call (`com`, `exe`, `bat` and `cmd` when unset or empty), followed by `""`. On other
+/// platforms, this is `""` followed by [`env::consts::EXE_EXTENSION`] when it isn't empty.
+pub fn executable_extensions() -> Vec<String> {
+    #[cfg(windows)]
+    let extensions = path_extensions()
+        .into_iter()
+        .chain(iter::once(String::new()));
+    #[cfg(not(windows))]
+    let extensions = iter::once(String::new()).chain(
+        Some(env::consts::EXE_EXTENSION)
+            .filter(|ext| !ext.is_empty())
+            .map(String::from),
+    );
+    extensions.collect()
+}
+
+// Lowercased `PATHEXT` extensions without the leading dot.
+#[cfg(windows)]
+fn path_extensions() -> Vec<String> {
+    let pathext = env::var("PATHEXT").unwrap_or_default();
+    let extensions: Vec<_> = pathext
+        .split(';')
+        .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect();
+    if extensions.is_empty() {
+        ["com", "exe", "bat", "cmd"].map(String::from).to_vec()
+    } else {
+        extensions
+    }
+}
+
+#[cfg(not(windows))]
+fn binary_candidates(path: Utf8PathBuf) -> impl Iterator<Item = Utf8PathBuf> {
+    let with_extension = match env::consts::EXE_EXTENSION {
+        "" => None,
+        it => Some(path.with_extension(it)),
+    };
+    iter::once(path).chain(with_extension)
+}
+
+/// The places an executable can be found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Source {
+    /// A directory listed in `PATH`.
+    Path,
+    /// The environment variable named after the executable, e.g. `$CARGO`.
+    Env,
+    /// `$CARGO_HOME/bin`, see [`find_in_cargo_home`].
+    CargoHome,
+}
+
+/// find_with_source_order return the first match for `exec` from `order`, tried in the given order,
+/// together with the source it came from, e.g. to drive precedence from a config file.
+///
+/// Each source is searched like its dedicated function: [`find_in_path`], [`find_in_env`] and
+/// [`find_in_cargo_home`]. A source listed twice is simply searched again, which is harmless, and
+/// an empty `order` finds nothing.
+pub fn find_with_source_order(exec: &str, order: &[Source]) -> Option<(Utf8PathBuf, Source)> {
+    order.iter().find_map(|&source| {
+        let found = match source {
+            Source::Path => find_in_path(exec),
+            Source::Env => find_in_env(exec),
+            Source::CargoHome => find_in_cargo_home(exec),
+        };
+        Some((found?, source))
+    })
+}
+
+/// A resolved executable together with a hash of the inputs that produced it, as returned by
+/// [`find_recorded`], for caching resolutions across runs.
+///
+/// With the `serde` feature, it can be serialized and deserialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResolvedInvocation {
+    /// The executable name that was searched for.
+    pub exec: String,
+    pub path: Utf8PathBuf,
+    pub source: Source,
+    /// See [`search_input_hash`].
+    pub input_hash: u64,
+}
+
+impl ResolvedInvocation {
+    /// is_stale return whether the search inputs changed since this record was made, i.e. whether
+    /// [`search_input_hash`] differs now, in which case the executable should be resolved again.
+    ///
+    /// Only the environment is compared: a binary installed or removed in an unchanged PATH goes
+    /// unnoticed.
+    pub fn is_stale(&self) -> bool {
+        search_input_hash(&self.exec) != self.input_hash
+    }
+}
+
+/// find_recorded return the executable resolved like [`find_with_cargo_home`] (PATH, environment
+/// variables and CARGO_HOME) as a [`ResolvedInvocation`] recording where it came from and the
+/// hash of the search inputs at the time of the call.
+pub fn find_recorded(exec: &str) -> Option<ResolvedInvocation> {
+    let exec = trim_exec(exec);
+    let (path, source) =
+        find_with_source_order(exec, &[Source::Path, Source::Env, Source::CargoHome])?;
+    Some(ResolvedInvocation {
+        exec: exec.to_owned(),
+        path,
+        source,
+        input_hash: search_input_hash(exec),
+    })
+}
+
+/// search_input_hash return a hash of everything from the environment that the lookup of
+/// [`find_recorded`] depends on: the executable name, `PATH`, `PATHEXT` on Windows, the
+/// variables named after the executable (see [`find_in_env`]) and the CARGO_HOME directory (see
+/// [`cargo_home_source`]).
+///
+/// Unset variables hash differently from empty ones. The hash is 64-bit FNV-1a over the raw
+/// values, so it is stable across runs, builds and Rust versions, but not across platforms. It
+/// says nothing about the contents of the directories.
+pub fn search_input_hash(exec: &str) -> u64 {
+    let exec = trim_exec(exec);
+    let mut vars = vec!["PATH".to_owned()];
+    if cfg!(windows) {
+        vars.push("PATHEXT".to_owned());
+    }
+    vars.extend(env_var_names(exec));
+    let cargo_home = get_cargo_home().map(|it| OsString::from(it.into_string()));
+    let inputs = iter::once(Some(OsString::from(exec)))
+        .chain(vars.into_iter().map(env::var_os))
+        .chain(iter::once(cargo_home));
+
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash = (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for input in inputs {
+        match input {
+            // Prefixed with the length, so "a" + "bc" and "ab" + "c" differ.
+            Some(value) => {
+                let bytes = value.as_encoded_bytes();
+                feed(&[1]);
+                feed(&(bytes.len() as u64).to_le_bytes());
+                feed(bytes);
+            }
+            None => feed(&[0]),
+        }
+    }
+    hash
+}
+
+/// with_scoped_env run `f` with the environment variables in `vars` set, restoring their previous
+/// values (or unsetting them) afterwards, even if `f` panics.
+///
+/// This runs a lookup "as if" PATH had a given value. The environment is process-global though:
+/// other threads observe the scoped values while `f` runs, and concurrent calls can interleave, so
+/// callers running lookups from several threads must serialize them themselves.
+pub fn with_scoped_env<T>(vars: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+    struct Restore(Vec<(String, Option<OsString>)>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            // In reverse, so the original value wins when a key is listed twice.
+            for (key, value) in self.0.drain(..).rev() {
+                match value {
+                    Some(value) => env::set_var(key, value),
+                    None => env::remove_var(key),
+                }
+            }
+        }
+    }
+
+    let _restore = Restore(
+        vars.iter()
+            .map(|(key, _)| (key.to_string(), env::var_os(key)))
+            .collect(),
+    );
+    for (key, value) in vars {
+        env::set_var(key, value);
+    }
+    f()
+}
+
+/// Where the CARGO_HOME used by [`find_in_cargo_home`] comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CargoHomeSource {
+    /// The `CARGO_HOME` environment variable is set.
+    EnvVar,
+    /// `CARGO_HOME` is unset and `~/.cargo` is used.
+    DefaultHome,
+}
+
+/// cargo_home_source return the CARGO_HOME used by [`find_in_cargo_home`] together with the branch
+/// of the resolution that produced it, e.g. to tell the user "no CARGO_HOME set; using ~/.cargo".
+pub fn cargo_home_source() -> Option<(Utf8PathBuf, CargoHomeSource)> {
+    if let Some(path) = env::var_os("CARGO_HOME") {
+        let path = Utf8PathBuf::try_from(PathBuf::from(path)).ok()?;
+        return Some((path, CargoHomeSource::EnvVar));
+    }
+
+    if let Some(mut path) = home::home_dir() {
+        path.push(".cargo");
+        let path = Utf8PathBuf::try_from(path).ok()?;
+        return Some((path, CargoHomeSource::DefaultHome));
+    }
+
+    None
+}
+
+/// validate return the executable at `path` if it exists, is a file, and is executable.
+///
+/// The candidate goes through [`probe_for_binary`], so the same extension logic as PATH matches
+/// applies, and on Unix at least one execute permission bit must be set.
+pub fn validate(path: &Utf8Path) -> Option<Utf8PathBuf> {
+    probe_for_binary(path.to_path_buf()).filter(|it| is_executable(it))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Utf8Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Utf8Path) -> bool {
+    path.is_file()
+}
+
+fn get_cargo_home() -> Option<Utf8PathBuf> {
+    cargo_home_source().map(|(path, _)| path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::sync::{Mutex, MutexGuard};
+    use tempfile::TempDir;
+
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    // Serializes the tests that rewrite PATH, which is shared by the whole process.
+    pub(crate) fn lock_env() -> MutexGuard<'static, ()> {
+        ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    #[test]
+    fn test_find_in_path() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let fake_bin = temp_dir.path().join("fake-binary");
+        fs::write(&fake_bin, "").unwrap();
+
+        let expected_path = Utf8PathBuf::try_from(fake_bin).unwrap();
+        with_scoped_env(&[("PATH", temp_dir.path().to_str().unwrap())], || {
+            assert_eq!(find_in_path("fake-binary"), Some(expected_path));
+            assert_eq!(find_in_path("non-existent-binary"), None);
+        });
+    }
+
+    #[test]
+    fn test_find_trailing_separator() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("fake-binary"), "").unwrap();
+        fs::write(dir.join("sub").join("fake-binary"), "").unwrap();
+
+        with_scoped_env(&[("PATH", dir.as_str())], || {
+            assert_eq!(find_in_path("fake-binary/"), Some(dir.join("fake-binary")));
+            assert_eq!(find("fake-binary//"), Some(dir.join("fake-binary")));
+            // Only the trailing separators are dropped.
+            assert_eq!(
+                find_in_path("sub/fake-binary/"),
+                Some(dir.join("sub/fake-binary"))
+            );
+            assert_eq!(find_in_path("sub/"), None);
+        });
+    }
+
+    #[test]
+    fn test_find_mapped() {
+        let _guard = lock_env();
+        let path_dir = TempDir::new().unwrap();
+        let override_dir = TempDir::new().unwrap();
+        let path_dir = Utf8PathBuf::try_from(path_dir.path().to_path_buf()).unwrap();
+        let override_dir = Utf8PathBuf::try_from(override_dir.path().to_path_buf()).unwrap();
+        fs::write(path_dir.join("fake-mapped"), "").unwrap();
+        fs::write(override_dir.join("fake-override"), "").unwrap();
+
+        let override_bin = override_dir.join("fake-override");
+        let vars = [
+            ("PATH", path_dir.as_str()),
+            ("TEST_MAPPED_OVERRIDE", override_bin.as_str()),
+            ("TEST_MAPPED_MISSING", "/non-existent/fake-mapped"),
+        ];
+        with_scoped_env(&vars, || {
+            let found = find_mapped(&[
+                ("fake-overridden", "TEST_MAPPED_OVERRIDE"),
+                ("fake-mapped", "TEST_MAPPED_MISSING"),
+                ("non-existent-binary", "TEST_MAPPED_UNSET"),
+            ]);
+            assert_eq!(found.len(), 3);
+            assert_eq!(found["fake-overridden"], Some(override_bin.clone()));
+            assert_eq!(found["fake-mapped"], Some(path_dir.join("fake-mapped")));
+            assert_eq!(found["non-existent-binary"], None);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_in_short_path() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::write(dir.join("fake-binary"), "").unwrap();
+
+        let paths = [
+            format!("/non-existent:{dir}"),
+            format!("{dir}/"),
+            format!(":{dir}:"),
+            String::new(),
+        ];
+        for paths in &paths {
+            with_scoped_env(&[("PATH", paths)], || {
+                for exec in ["fake-binary", "non-existent-binary"] {
+                    let fast = find_in_short_path(OsStr::new(paths), exec);
+                    assert_eq!(fast, Some(find_in_path_dirs(exec)), "{paths:?}");
+                }
+            });
+        }
+
+        // Repeated separators are left to the general search, which normalizes them away.
+        let paths = format!("/non-existent:{}", dir.as_str().replacen('/', "//", 2));
+        with_scoped_env(&[("PATH", &paths)], || {
+            assert_eq!(find_in_short_path(OsStr::new(&paths), "fake-binary"), None);
+            assert_eq!(find_in_path("fake-binary"), Some(dir.join("fake-binary")));
+        });
+    }
+
+    #[test]
+    fn test_find_in_path_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::create_dir(dir.join("first")).unwrap();
+        fs::create_dir(dir.join("second")).unwrap();
+        fs::write(dir.join("second/fake-binary"), "").unwrap();
+
+        let path_file = dir.join("path.txt");
+        fs::write(
+            &path_file,
+            format!("{}\n\n{}\n", dir.join("first"), dir.join("second")),
+        )
+        .unwrap();
+
+        assert_eq!(
+            find_in_path_file(&path_file, "fake-binary").unwrap(),
+            Some(dir.join("second/fake-binary"))
+        );
+        assert_eq!(
+            find_in_path_file(&path_file, "non-existent-binary").unwrap(),
+            None
+        );
+        assert!(find_in_path_file(&dir.join("non-existent.txt"), "fake-binary").is_err());
+    }
+
+    #[test]
+    fn test_find_ranked_extensions() {
+        let _guard = lock_env();
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let first_dir = Utf8PathBuf::try_from(first.path().to_path_buf()).unwrap();
+        let second_dir = Utf8PathBuf::try_from(second.path().to_path_buf()).unwrap();
+        fs::write(first_dir.join("fake-tool.sh"), "").unwrap();
+        fs::write(first_dir.join("fake-tool.py"), "").unwrap();
+        fs::write(second_dir.join("fake-tool"), "").unwrap();
+
+        let paths = env::join_paths([&first_dir, &second_dir]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(
+                find_ranked_extensions("fake-tool", &["", "sh", "py"]),
+                Some(first_dir.join("fake-tool.sh"))
+            );
+            assert_eq!(
+                find_ranked_extensions("fake-tool", &["py", "sh"]),
+                Some(first_dir.join("fake-tool.py"))
+            );
+            assert_eq!(
+                find_ranked_extensions("fake-tool", &[""]),
+                Some(second_dir.join("fake-tool"))
+            );
+            assert_eq!(find_ranked_extensions("fake-tool", &["rb"]), None);
+            assert_eq!(
+                find_ranked_extensions("fake-tool", &[".rb", ".py", "sh"]),
+                Some(first_dir.join("fake-tool.py"))
+            );
+            assert_eq!(
+                find_ranked_extensions("fake-tool", &[".", "sh"]),
+                Some(first_dir.join("fake-tool.sh"))
+            );
+        });
+    }
+
+    #[test]
+    fn test_find_with_index() {
+        let _guard = lock_env();
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        fs::write(second.path().join("fake-binary"), "").unwrap();
+
+        let expected_path = Utf8PathBuf::try_from(second.path().join("fake-binary")).unwrap();
+        let paths = env::join_paths([first.path(), second.path()]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(find_with_index("fake-binary"), Some((expected_path, 1)));
+            assert_eq!(find_with_index("non-existent-binary"), None);
+        });
+    }
+
+    #[test]
+    fn test_find_newer_than() {
+        let _guard = lock_env();
+        let old = TempDir::new().unwrap();
+        let new = TempDir::new().unwrap();
+        let old_bin = Utf8PathBuf::try_from(old.path().join("fake-binary")).unwrap();
+        let new_bin = Utf8PathBuf::try_from(new.path().join("fake-binary")).unwrap();
+        let reference = SystemTime::now() - std::time::Duration::from_secs(60);
+        for (path, modified) in [(&old_bin, reference), (&new_bin, SystemTime::now())] {
+            fs::File::create(path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+
+        let paths = env::join_paths([old.path(), new.path()]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(find_newer_than("fake-binary", reference), Some(new_bin));
+            assert_eq!(find_newer_than("fake-binary", SystemTime::now()), None);
+            assert_eq!(find_newer_than("non-existent-binary", reference), None);
+        });
+    }
+
+    #[test]
+    fn test_find_checked_freshest() {
+        let _guard = lock_env();
+        let old = TempDir::new().unwrap();
+        let new = TempDir::new().unwrap();
+        let old_bin = Utf8PathBuf::try_from(old.path().join("fake-binary")).unwrap();
+        let new_bin = Utf8PathBuf::try_from(new.path().join("fake-binary")).unwrap();
+        let now = SystemTime::now();
+        let earlier = now - std::time::Duration::from_secs(60);
+        let set_modified = |path: &Utf8PathBuf, modified| {
+            fs::File::options()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        set_modified(&old_bin, earlier);
+        set_modified(&new_bin, now);
+
+        let paths = env::join_paths([old.path(), new.path()]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(
+                find_checked_freshest("fake-binary"),
+                (Some(old_bin.clone()), false)
+            );
+            set_modified(&old_bin, now);
+            assert_eq!(
+                find_checked_freshest("fake-binary"),
+                (Some(old_bin.clone()), false)
+            );
+            set_modified(&new_bin, earlier);
+            assert_eq!(
+                find_checked_freshest("fake-binary"),
+                (Some(old_bin.clone()), true)
+            );
+            assert_eq!(find_checked_freshest("non-existent-binary"), (None, true));
+        });
+
+        // A symlinked PATH directory lists the same file twice, with the same modified time.
+        #[cfg(unix)]
+        {
+            let link = new.path().join("link");
+            std::os::unix::fs::symlink(old.path(), &link).unwrap();
+            let paths = env::join_paths([old.path(), link.as_path()]).unwrap();
+            with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+                assert_eq!(
+                    find_checked_freshest("fake-binary"),
+                    (Some(old_bin.clone()), true)
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn test_compare_sources() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let fake_bin = dir.join("fake-compared");
+        let other_bin = dir.join("other-compared");
+        fs::write(&fake_bin, "").unwrap();
+        fs::write(&other_bin, "").unwrap();
+        let canonical_bin = fake_bin.canonicalize_utf8().unwrap();
+
+        let same = dir.join(".").join("fake-compared");
+        with_scoped_env(
+            &[("PATH", dir.as_str()), ("FAKE-COMPARED", same.as_str())],
+            || {
+                assert_eq!(
+                    compare_sources("fake-compared"),
+                    SourceComparison::Same(canonical_bin)
+                );
+            },
+        );
+        let vars = [
+            ("PATH", dir.as_str()),
+            ("FAKE-COMPARED", other_bin.as_str()),
+        ];
+        with_scoped_env(&vars, || {
+            assert_eq!(
+                compare_sources("fake-compared"),
+                SourceComparison::Different {
+                    path: fake_bin.clone(),
+                    env: other_bin.clone(),
+                }
+            );
+        });
+        with_scoped_env(&[("PATH", dir.as_str())], || {
+            assert_eq!(
+                compare_sources("fake-compared"),
+                SourceComparison::OnlyPath(fake_bin.clone())
+            );
+            assert_eq!(
+                compare_sources("non-existent-binary"),
+                SourceComparison::Neither
+            );
+        });
+        with_scoped_env(
+            &[("PATH", ""), ("FAKE-COMPARED", fake_bin.as_str())],
+            || {
+                assert_eq!(
+                    compare_sources("fake-compared"),
+                    SourceComparison::OnlyEnv(fake_bin.clone())
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_find_unambiguous() {
+        let _guard = lock_env();
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        fs::write(first.path().join("fake-binary"), "").unwrap();
+        fs::write(second.path().join("fake-binary"), "").unwrap();
+
+        let expected_path = Utf8PathBuf::try_from(first.path().join("fake-binary")).unwrap();
+        with_scoped_env(&[("PATH", first.path().to_str().unwrap())], || {
+            assert_eq!(find_unambiguous("fake-binary"), Ok(expected_path.clone()));
+            assert_eq!(
+                find_unambiguous("non-existent-binary"),
+                Err(AmbiguityError::NotFound)
+            );
+        });
+
+        let paths = env::join_paths([first.path(), first.path()]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(find_unambiguous("fake-binary"), Ok(expected_path.clone()));
+        });
+
+        let paths = env::join_paths([first.path(), second.path()]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(
+                find_unambiguous("fake-binary"),
+                Err(AmbiguityError::Ambiguous(vec![
+                    expected_path.clone(),
+                    Utf8PathBuf::try_from(second.path().join("fake-binary")).unwrap(),
+                ]))
+            );
+        });
+
+        #[cfg(unix)]
+        {
+            let linked = TempDir::new().unwrap();
+            std::os::unix::fs::symlink(
+                first.path().join("fake-binary"),
+                linked.path().join("fake-binary"),
+            )
+            .unwrap();
+            let paths = env::join_paths([first.path(), linked.path()]).unwrap();
+            with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+                assert_eq!(find_unambiguous("fake-binary"), Ok(expected_path));
+            });
+        }
+    }
+
+    #[test]
+    fn test_is_on_path() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::create_dir(dir.join("sub")).unwrap();
+
+        with_scoped_env(&[("PATH", dir.as_str())], || {
+            assert!(is_on_path(&dir.join("fake-binary")));
+            assert!(is_on_path(&dir.join("sub/../fake-binary")));
+            assert!(is_on_path(&dir.join("./fake-binary")));
+            assert!(!is_on_path(&dir.join("sub/fake-binary")));
+            assert!(!is_on_path(Utf8Path::new("fake-binary")));
+        });
+    }
+
+    #[test]
+    fn test_with_scoped_env() {
+        let _guard = lock_env();
+        env::set_var("TESTSCOPEDSET", "before");
+        env::remove_var("TESTSCOPEDUNSET");
+
+        let result = std::panic::catch_unwind(|| {
+            with_scoped_env(
+                &[("TESTSCOPEDSET", "during"), ("TESTSCOPEDUNSET", "during")],
+                || {
+                    assert_eq!(env::var("TESTSCOPEDSET").unwrap(), "during");
+                    assert_eq!(env::var("TESTSCOPEDUNSET").unwrap(), "during");
+                    panic!("restores even on panic");
+                },
+            )
+        });
+
+        assert!(result.is_err());
+        assert_eq!(env::var("TESTSCOPEDSET").unwrap(), "before");
+        assert!(env::var_os("TESTSCOPEDUNSET").is_none());
+        env::remove_var("TESTSCOPEDSET");
+    }
+
+    #[test]
+    fn test_find_in_env() {
+        env::set_var("TESTEXEC", "/path/to/testexec");
+        assert!(find_in_env("testexec").is_some());
+        assert!(find_in_env("nonexistent").is_none());
+        env::remove_var("TESTEXEC");
+    }
+
+    #[test]
+    fn test_find_in_env_quoted() {
+        let _guard = lock_env();
+        let find_with =
+            |value| with_scoped_env(&[("TESTQUOTED", value)], || find_in_env("testquoted"));
+        assert_eq!(
+            find_with("\"/path/to/testquoted\""),
+            Some(Utf8PathBuf::from("/path/to/testquoted"))
+        );
+        assert_eq!(
+            find_with("'/path/to/testquoted'"),
+            Some(Utf8PathBuf::from("/path/to/testquoted"))
+        );
+        assert_eq!(
+            find_with("\"/path/to/testquoted"),
+            Some(Utf8PathBuf::from("\"/path/to/testquoted"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_in_env_lowercase() {
+        let _guard = lock_env();
+        with_scoped_env(&[("testlowerexec", "/path/to/testlowerexec")], || {
+            assert_eq!(
+                find_in_env("testlowerexec"),
+                Some(Utf8PathBuf::from("/path/to/testlowerexec"))
+            );
+            assert_eq!(
+                find_in_env("TestLowerExec"),
+                Some(Utf8PathBuf::from("/path/to/testlowerexec"))
+            );
+
+            with_scoped_env(&[("TESTLOWEREXEC", "/path/to/TESTLOWEREXEC")], || {
+                assert_eq!(
+                    find_in_env("testlowerexec"),
+                    Some(Utf8PathBuf::from("/path/to/TESTLOWEREXEC"))
+                );
+            });
+        });
+    }
+
+    #[test]
+    fn test_find_dir() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let fake_bin = dir.join("fakedirbinary");
+        fs::write(&fake_bin, "").unwrap();
+
+        with_scoped_env(&[("FAKEDIRBINARY", fake_bin.as_str())], || {
+            assert_eq!(find_dir("fakedirbinary"), Some(dir.clone()));
+        });
+        with_scoped_env(&[("FAKEDIRBINARY", "fakedirbinary")], || {
+            assert_eq!(find_dir("fakedirbinary"), None);
+        });
+
+        assert_eq!(find_dir("non-existent-binary"), None);
+    }
+
+    #[test]
+    fn test_find_install_prefix() {
+        let _guard = lock_env();
+        let find_with = |value| {
+            with_scoped_env(&[("FAKEPREFIXBINARY", value)], || {
+                find_install_prefix("fakeprefixbinary")
+            })
+        };
+        assert_eq!(
+            find_with("/opt/tool/bin/fakeprefixbinary"),
+            Some(Utf8PathBuf::from("/opt/tool"))
+        );
+        assert_eq!(
+            find_with("/opt/tool/libexec/fakeprefixbinary"),
+            Some(Utf8PathBuf::from("/opt/tool/libexec"))
+        );
+        assert_eq!(
+            find_with("bin/fakeprefixbinary"),
+            Some(Utf8PathBuf::from("bin"))
+        );
+        assert_eq!(
+            find_with("tool/bin/fakeprefixbinary"),
+            Some(Utf8PathBuf::from("tool"))
+        );
+    }
+
+    #[test]
+    fn test_find_invocation() {
+        let _guard = lock_env();
+        with_scoped_env(&[("TESTINVOCATION", "/path/to/testinvocation")], || {
+            assert_eq!(
+                find_invocation("testinvocation"),
+                Some(Invocation {
+                    program: Utf8PathBuf::from("/path/to/testinvocation"),
+                    args: Vec::new(),
+                })
+            );
+        });
+
+        assert_eq!(find_invocation("non-existent-binary"), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_find_invocation_batch_script() {
+        let _guard = lock_env();
+        with_scoped_env(&[("TESTBATCH", r"C:\tools\testbatch.cmd")], || {
+            let invocation = find_invocation("testbatch").unwrap();
+            assert!(invocation
+                .program
+                .as_str()
+                .to_ascii_lowercase()
+                .ends_with("cmd.exe"));
+            assert_eq!(invocation.args, ["/C", r"C:\tools\testbatch.cmd"]);
+        });
+    }
+
+    #[test]
+    fn test_find_near() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::create_dir_all(dir.join("libexec")).unwrap();
+        fs::write(dir.join("bin/fake-anchor"), "").unwrap();
+        fs::write(dir.join("bin/fake-sibling"), "").unwrap();
+        fs::write(dir.join("libexec/fake-helper"), "").unwrap();
+
+        let anchor = dir.join("bin/fake-anchor");
+        assert_eq!(
+            find_near(&anchor, "fake-sibling"),
+            Some(dir.join("bin/fake-sibling"))
+        );
+        assert_eq!(
+            find_near(&dir.join("libexec/fake-helper"), "fake-sibling"),
+            Some(dir.join("bin/fake-sibling"))
+        );
+        assert_eq!(find_near(&anchor, "fake-helper"), None);
+    }
+
+    #[test]
+    fn test_find_near_any() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let [stable, nightly] = [dir.join("stable"), dir.join("nightly")];
+        for toolchain in [&stable, &nightly] {
+            fs::create_dir_all(toolchain.join("bin")).unwrap();
+            fs::create_dir_all(toolchain.join("libexec")).unwrap();
+            fs::write(toolchain.join("bin/fake-rustc"), "").unwrap();
+        }
+        fs::write(nightly.join("libexec/fake-helper"), "").unwrap();
+        fs::write(stable.join("bin/fake-sibling"), "").unwrap();
+        fs::write(nightly.join("bin/fake-sibling"), "").unwrap();
+
+        let anchors = [
+            stable.join("bin/fake-rustc"),
+            nightly.join("bin/fake-rustc"),
+        ];
+        assert_eq!(
+            find_near_any(&anchors, "fake-helper"),
+            Some(nightly.join("libexec/fake-helper"))
+        );
+        assert_eq!(
+            find_near_any(&anchors, "fake-sibling"),
+            Some(stable.join("bin/fake-sibling"))
+        );
+        assert_eq!(find_near_any(&anchors, "non-existent-binary"), None);
+        assert_eq!(find_near_any(&[], "fake-sibling"), None);
+    }
+
+    #[test]
+    fn test_find_self() {
+        let found = find_self().unwrap();
+        assert!(found.is_absolute());
+        assert!(found.is_file());
+        assert_eq!(found, found.canonicalize_utf8().unwrap());
+    }
+
+    #[test]
+    fn test_find_in_install_tree() {
+        let exe = find_self().unwrap();
+        let dir_name = exe.parent().unwrap().file_name().unwrap();
+        let exec = exe.file_stem().unwrap();
+        assert_eq!(
+            find_in_install_tree(exec, &["missing", &format!("../{dir_name}")]),
+            Some(exe.clone())
+        );
+        assert_eq!(find_in_install_tree(exec, &["missing"]), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_find_in_cargo_home_pathext() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let cargo_home = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::create_dir_all(cargo_home.join("bin")).unwrap();
+        fs::write(cargo_home.join("bin/fake-cargo-binary.exe"), "").unwrap();
+        fs::write(cargo_home.join("bin/fake-cargo-shim.cmd"), "").unwrap();
+
+        with_scoped_env(&[("CARGO_HOME", cargo_home.as_str())], || {
+            assert_eq!(
+                find_in_cargo_home("fake-cargo-binary"),
+                Some(cargo_home.join("bin/fake-cargo-binary.exe"))
+            );
+            assert_eq!(
+                find_in_cargo_home("fake-cargo-shim"),
+                Some(cargo_home.join("bin/fake-cargo-shim.cmd"))
+            );
+        });
+    }
+
+    #[test]
+    fn test_find_in_virtualenv() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let venv = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let scripts = venv.join(if cfg!(windows) { "Scripts" } else { "bin" });
+        fs::create_dir_all(&scripts).unwrap();
+        fs::write(
+            scripts.join(format!("fake-venv-binary{}", env::consts::EXE_SUFFIX)),
+            "",
+        )
+        .unwrap();
+
+        with_scoped_env(&[("VIRTUAL_ENV", venv.as_str())], || {
+            assert_eq!(
+                find_in_virtualenv("fake-venv-binary"),
+                Some(scripts.join(format!("fake-venv-binary{}", env::consts::EXE_SUFFIX)))
+            );
+            assert_eq!(find_in_virtualenv("non-existent-binary"), None);
+            env::remove_var("VIRTUAL_ENV");
+            assert_eq!(find_in_virtualenv("fake-venv-binary"), None);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_try_find_non_utf8_match() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join(OsStr::from_bytes(b"non-utf8-\xff"));
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("fake-non-utf8-binary"), "").unwrap();
+
+        with_scoped_env(&[("PATH", "")], || {
+            env::set_var("PATH", &dir);
+            assert_eq!(find("fake-non-utf8-binary"), None);
+            match try_find("fake-non-utf8-binary") {
+                Err(FindError::NonUtf8Match { lossy }) => {
+                    assert!(lossy.ends_with("non-utf8-\u{FFFD}/fake-non-utf8-binary"))
+                }
+                other => panic!("unexpected result: {other:?}"),
+            }
+            assert_eq!(try_find("non-existent-binary"), Err(FindError::NotFound));
+        });
+    }
+
+    #[test]
+    fn test_find_in_dirs_lazy() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let binary = dir.join(format!("fake-lazy-binary{}", env::consts::EXE_SUFFIX));
+        fs::write(&binary, "").unwrap();
+
+        let mut pulled = Vec::new();
+        let dirs = [dir.join("missing"), dir.clone(), dir.join("never")]
+            .into_iter()
+            .inspect(|dir| pulled.push(dir.clone()));
+        assert_eq!(find_in_dirs_lazy("fake-lazy-binary", dirs), Some(binary));
+        assert_eq!(pulled, [dir.join("missing"), dir.clone()]);
+    }
+
+    #[test]
+    fn test_find_all() {
+        let _guard = lock_env();
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let first = Utf8PathBuf::try_from(first.path().to_path_buf()).unwrap();
+        let second = Utf8PathBuf::try_from(second.path().to_path_buf()).unwrap();
+        let name = format!("fake-all-binary{}", env::consts::EXE_SUFFIX);
+        fs::write(first.join(&name), "").unwrap();
+        fs::write(second.join(&name), "").unwrap();
+
+        let paths = env::join_paths([&second, &first, &second]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(
+                find_all("fake-all-binary"),
+                [second.join(&name), first.join(&name)]
+            );
+            assert!(find_all("non-existent-binary").is_empty());
+        });
+    }
+
+    #[test]
+    fn test_executable_extensions() {
+        let _guard = lock_env();
+        if cfg!(windows) {
+            with_scoped_env(&[("PATHEXT", ".EXE;.Cmd")], || {
+                assert_eq!(executable_extensions(), ["exe", "cmd", ""]);
+            });
+        } else {
+            assert_eq!(executable_extensions(), [""]);
+        }
+    }
+
+    #[test]
+    fn test_find_or_else() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let binary = dir.join(format!("fake-fallback-binary{}", env::consts::EXE_SUFFIX));
+        fs::write(&binary, "").unwrap();
+
+        with_scoped_env(&[("PATH", dir.as_str())], || {
+            assert_eq!(
+                find_or_else("fake-fallback-binary", || panic!("fallback called")),
+                Some(binary.clone())
+            );
+        });
+        with_scoped_env(&[("PATH", "")], || {
+            assert_eq!(
+                find_or_else("fake-fallback-binary", || Some(
+                    dir.join("fake-fallback-binary")
+                )),
+                Some(binary.clone())
+            );
+            assert_eq!(
+                find_or_else("fake-fallback-binary", || Some(dir.join("missing"))),
+                None
+            );
+            assert_eq!(
+                find_or_else("fake-fallback-binary", || Some(dir.clone())),
+                None
+            );
+        });
+    }
+
+    #[test]
+    fn test_find_in_path_var() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let binary = dir.join(format!("fake-plugin{}", env::consts::EXE_SUFFIX));
+        fs::write(&binary, "").unwrap();
+
+        let paths = env::join_paths([dir.join("missing"), dir.clone()]).unwrap();
+        with_scoped_env(&[("FAKE_PLUGIN_PATH", paths.to_str().unwrap())], || {
+            assert_eq!(
+                find_in_path_var("FAKE_PLUGIN_PATH", "fake-plugin"),
+                Some(binary)
+            );
+            assert_eq!(
+                find_in_path_var("FAKE_PLUGIN_PATH", "non-existent-binary"),
+                None
+            );
+        });
+        assert_eq!(find_in_path_var("FAKE_PLUGIN_PATH", "fake-plugin"), None);
+    }
+
+    #[test]
+    fn test_find_and_open() {
+        use std::io::Read;
+
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let name = format!("fake-open-binary{}", env::consts::EXE_SUFFIX);
+        fs::create_dir(dir.join("dir")).unwrap();
+        fs::create_dir(dir.join("dir").join(&name)).unwrap();
+        fs::write(dir.join(&name), "original").unwrap();
+
+        let paths = env::join_paths([dir.join("dir"), dir.clone()]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            let (path, mut file) = find_and_open("fake-open-binary").unwrap();
+            assert_eq!(path, dir.join(&name));
+
+            #[cfg(unix)]
+            {
+                fs::write(dir.join("replacement"), "replaced").unwrap();
+                fs::rename(dir.join("replacement"), &path).unwrap();
+            }
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).unwrap();
+            assert_eq!(contents, "original");
+
+            assert!(find_and_open("non-existent-binary").is_none());
+        });
+    }
+
+    #[test]
+    fn test_find_relative_to() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let root = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let name = format!("fake-relative-binary{}", env::consts::EXE_SUFFIX);
+        fs::create_dir_all(root.join("project/tools/bin")).unwrap();
+        fs::write(root.join("project/tools/bin").join(&name), "").unwrap();
+
+        let path = root.join("project/tools/./bin");
+        with_scoped_env(&[("PATH", path.as_str())], || {
+            assert_eq!(
+                find_relative_to(&root.join("project"), "fake-relative-binary"),
+                Some(Utf8Path::new("tools").join("bin").join(&name))
+            );
+            assert_eq!(
+                find_relative_to(&root.join("elsewhere"), "fake-relative-binary"),
+                Some(normalize(&root.join("project/tools/bin").join(&name)))
+            );
+        });
+    }
+
+    #[test]
+    fn test_find_in_homebrew() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let prefix = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let suffix = env::consts::EXE_SUFFIX;
+        fs::create_dir_all(prefix.join("bin")).unwrap();
+        fs::create_dir_all(prefix.join("sbin")).unwrap();
+        fs::write(prefix.join(format!("bin/fake-brew-binary{suffix}")), "").unwrap();
+        fs::write(prefix.join(format!("sbin/fake-brew-binary{suffix}")), "").unwrap();
+        fs::write(prefix.join(format!("sbin/fake-brew-daemon{suffix}")), "").unwrap();
+
+        with_scoped_env(&[("HOMEBREW_PREFIX", prefix.as_str())], || {
+            assert_eq!(
+                find_in_homebrew("fake-brew-binary"),
+                Some(prefix.join(format!("bin/fake-brew-binary{suffix}")))
+            );
+            assert_eq!(
+                find_in_homebrew("fake-brew-daemon"),
+                Some(prefix.join(format!("sbin/fake-brew-daemon{suffix}")))
+            );
+            assert_eq!(find_in_homebrew("non-existent-binary"), None);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_try_find_permission_denied() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _guard = lock_env();
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let first = Utf8PathBuf::try_from(first.path().to_path_buf()).unwrap();
+        let second = Utf8PathBuf::try_from(second.path().to_path_buf()).unwrap();
+        for (dir, mode) in [(&first, 0o644), (&second, 0o755)] {
+            let path = dir.join("fake-denied-binary");
+            fs::write(&path, "").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        let paths = env::join_paths([&first, &second]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(
+                try_find("fake-denied-binary"),
+                Ok(second.join("fake-denied-binary"))
+            );
+        });
+        with_scoped_env(&[("PATH", first.as_str())], || {
+            assert_eq!(
+                try_find("fake-denied-binary"),
+                Err(FindError::PermissionDenied {
+                    path: first.join("fake-denied-binary")
+                })
+            );
+        });
+    }
+
+    #[test]
+    fn test_find_with_source_order() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let name = format!("fake-ordered-binary{}", env::consts::EXE_SUFFIX);
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::write(dir.join(&name), "").unwrap();
+        fs::write(dir.join("bin").join(&name), "").unwrap();
+
+        let vars = [
+            ("PATH", dir.as_str()),
+            ("FAKE-ORDERED-BINARY", "/path/to/env"),
+            ("CARGO_HOME", dir.as_str()),
+        ];
+        with_scoped_env(&vars, || {
+            let find = |order: &[Source]| find_with_source_order("fake-ordered-binary", order);
+            assert_eq!(
+                find(&[Source::Env, Source::CargoHome, Source::Path]),
+                Some(("/path/to/env".into(), Source::Env))
+            );
+            assert_eq!(
+                find(&[Source::CargoHome, Source::CargoHome, Source::Path]),
+                Some((dir.join("bin").join(&name), Source::CargoHome))
+            );
+            assert_eq!(
+                find(&[Source::Path, Source::Env]),
+                Some((dir.join(&name), Source::Path))
+            );
+            assert_eq!(find(&[]), None);
+        });
+    }
+
+    #[test]
+    fn test_find_arch_variant() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let suffix = env::consts::EXE_SUFFIX;
+        let arch = env::consts::ARCH;
+        let variant = dir.join(format!("fake-arch-binary-{arch}{suffix}"));
+        fs::write(&variant, "").unwrap();
+        fs::write(dir.join(format!("fake-arch-binary{suffix}")), "").unwrap();
+        fs::write(dir.join(format!("fake-arch-bare{suffix}")), "").unwrap();
+
+        with_scoped_env(&[("PATH", dir.as_str())], || {
+            assert_eq!(find_arch_variant("fake-arch-binary"), Some(variant));
+            assert_eq!(
+                find_arch_variant("fake-arch-bare"),
+                Some(dir.join(format!("fake-arch-bare{suffix}")))
+            );
+        });
+    }
+
+    #[test]
+    fn test_find_platform_variant() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        fs::write(dir.join("fake-helper.sh"), "").unwrap();
+        fs::write(dir.join("fake-helper.bat"), "").unwrap();
+        let expected = if cfg!(windows) {
+            dir.join("fake-helper.bat")
+        } else {
+            dir.join("fake-helper.sh")
+        };
+
+        with_scoped_env(&[("PATH", dir.as_str())], || {
+            assert_eq!(
+                find_platform_variant("fake-helper", "sh", ".bat"),
+                Some(expected.clone())
+            );
+            assert_eq!(
+                find_platform_variant("fake-helper", ".sh", "bat"),
+                Some(expected.clone())
+            );
+            assert_eq!(find_platform_variant("fake-helper", "", "cmd"), None);
+        });
+    }
+
+    #[test]
+    fn test_find_counted() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let binary = dir.join(format!("fake-counted-binary{}", env::consts::EXE_SUFFIX));
+        fs::write(&binary, "").unwrap();
+
+        let per_dir = executable_extensions().len();
+        let paths = env::join_paths([dir.join("missing"), dir.join("other"), dir.clone()]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            let (found, probes) = find_counted("fake-counted-binary");
+            assert_eq!(found, Some(binary));
+            assert!(probes > 2 * per_dir && probes <= 3 * per_dir);
+            assert_eq!(find_counted("non-existent-binary"), (None, 3 * per_dir));
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_find_in_flatpak_exports() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let home = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let exports = home.join(".local/share/flatpak/exports/bin");
+        fs::create_dir_all(&exports).unwrap();
+        fs::write(exports.join("org.example.FakeApp"), "").unwrap();
+
+        with_scoped_env(&[("HOME", home.as_str())], || {
+            assert_eq!(
+                find_in_flatpak_exports("org.example.FakeApp"),
+                Some(exports.join("org.example.FakeApp"))
+            );
+            assert_eq!(find_in_flatpak_exports("non-existent-binary"), None);
+        });
+    }
+
+    #[test]
+    fn test_path_dirs_normalized() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let name = format!("fake-normalized-binary{}", env::consts::EXE_SUFFIX);
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("bin").join(&name), "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let executable = fs::Permissions::from_mode(0o755);
+            fs::set_permissions(dir.join("bin").join(&name), executable).unwrap();
+        }
+
+        let paths = env::join_paths([
+            dir.join("sub").join("..").join("bin"),
+            dir.join(".").join("bin"),
+            dir.join("bin"),
+        ])
+        .unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(path_dirs(), [dir.join("bin")]);
+            assert_eq!(
+                find_in_path("fake-normalized-binary"),
+                Some(dir.join("bin").join(&name))
+            );
+            assert_eq!(
+                find_all("fake-normalized-binary"),
+                [dir.join("bin").join(&name)]
+            );
+
+            let binary = dir.join("bin").join(&name);
+            assert_eq!(
+                find_with_index("fake-normalized-binary"),
+                Some((binary.clone(), 0))
+            );
+            assert_eq!(try_find("fake-normalized-binary"), Ok(binary.clone()));
+            assert_eq!(find_explained("fake-normalized-binary"), Ok(binary.clone()));
+            assert_eq!(
+                find_in_path_var("PATH", "fake-normalized-binary"),
+                Some(binary.clone())
+            );
+            let audited: Vec<_> = audit("fake-normalized-binary")
+                .into_iter()
+                .map(|it| (it.path, it.index))
+                .collect();
+            assert_eq!(
+                audited,
+                [0, 1, 2].map(|index| (binary.clone(), Some(index)))
+            );
+        });
+    }
+
+    #[test]
+    fn test_find_best() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let name = format!("fake-ranked-binary{}", env::consts::EXE_SUFFIX);
+        let dirs = [dir.join("a"), dir.join("b"), dir.join("c")];
+        for (dir, contents) in dirs.iter().zip(["", "big binary", "big binary"]) {
+            fs::create_dir(dir).unwrap();
+            fs::write(dir.join(&name), contents).unwrap();
+        }
+
+        let paths = env::join_paths(&dirs).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            let by_size = |found: &FoundBinary| found.metadata.len() as i64;
+            assert_eq!(
+                find_best("fake-ranked-binary", by_size),
+                Some(dirs[1].join(&name))
+            );
+            let in_c = |found: &FoundBinary| i64::from(found.path.starts_with(&dirs[2]));
+            assert_eq!(
+                find_best("fake-ranked-binary", in_c),
+                Some(dirs[2].join(&name))
+            );
+            assert_eq!(find_best("non-existent-binary", by_size), None);
+        });
+    }
+
+    #[test]
+    fn test_plan() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        // Neither directory exists, which the plan doesn't check.
+        let [first, second] = [dir.join("first"), dir.join("second")];
+        let paths = env::join_paths([&first, &second, &first]).unwrap();
+        let variants = |dir: &Utf8PathBuf| {
+            let mut variants = vec![(Source::Path, dir.join("fake-plan"))];
+            if cfg!(windows) {
+                variants.insert(0, (Source::Path, dir.join("fake-plan.exe")));
+            }
+            variants
+        };
+
+        let vars = [
+            ("PATH", paths.to_str().unwrap()),
+            ("PATHEXT", ".EXE"),
+            ("FAKE-PLAN", "/opt/fake-plan"),
+        ];
+        with_scoped_env(&vars, || {
+            let mut expected = variants(&first);
+            expected.extend(variants(&second));
+            expected.push((Source::Env, "/opt/fake-plan".into()));
+            assert_eq!(plan("fake-plan").candidates, expected);
+        });
+    }
+
+    #[test]
+    fn test_find_for_command() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let binary = dir.join(format!("fake-command-binary{}", env::consts::EXE_SUFFIX));
+        fs::write(&binary, "").unwrap();
+
+        let vars = [("PATH", ""), ("FAKE-INHERITED", "/inherited")];
+        with_scoped_env(&vars, || {
+            let mut cmd = Command::new("true");
+            cmd.env("PATH", &dir);
+            assert_eq!(
+                find_for_command(&cmd, "fake-command-binary"),
+                Some(binary.clone())
+            );
+            assert_eq!(find("fake-command-binary"), None);
+            assert_eq!(
+                find_for_command(&cmd, "fake-inherited"),
+                Some("/inherited".into())
+            );
+            cmd.env_remove("FAKE-INHERITED");
+            assert_eq!(find_for_command(&cmd, "fake-inherited"), None);
+            cmd.env("FAKE-INHERITED", "/overridden");
+            assert_eq!(
+                find_for_command(&cmd, "fake-inherited"),
+                Some("/overridden".into())
+            );
+        });
+    }
+
+    #[test]
+    fn test_store_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().canonicalize().unwrap()).unwrap();
+        let store_dir = dir.join("node_modules/.pnpm/tool@1.0.0/bin");
+        fs::create_dir_all(&store_dir).unwrap();
+        fs::write(store_dir.join("tool"), "").unwrap();
+        fs::write(dir.join("local-tool"), "").unwrap();
+
+        assert_eq!(
+            store_path(&store_dir.join("tool")),
+            Some(store_dir.join("tool"))
+        );
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(store_dir.join("tool"), dir.join("tool")).unwrap();
+            assert_eq!(store_path(&dir.join("tool")), Some(store_dir.join("tool")));
+        }
+        assert_eq!(store_path(&dir.join("local-tool")), None);
+        assert_eq!(store_path(&dir.join("missing")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_distinct() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().canonicalize().unwrap()).unwrap();
+        let [real, linked, copy] = [dir.join("real"), dir.join("linked"), dir.join("copy")];
+        for sub in [&real, &linked, &copy] {
+            fs::create_dir(sub).unwrap();
+        }
+        fs::write(real.join("fake-distinct"), "").unwrap();
+        fs::write(copy.join("fake-distinct"), "").unwrap();
+        std::os::unix::fs::symlink(real.join("fake-distinct"), linked.join("fake-distinct"))
+            .unwrap();
+
+        let paths = env::join_paths([&linked, &real, &copy]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(find_all("fake-distinct").len(), 3);
+            assert_eq!(
+                find_distinct("fake-distinct"),
+                [real.join("fake-distinct"), copy.join("fake-distinct")]
+            );
+            assert!(find_distinct("non-existent-binary").is_empty());
+        });
+    }
+
+    #[test]
+    fn test_find_grouped() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let [first, empty, second] = [dir.join("first"), dir.join("empty"), dir.join("second")];
+        let name = format!("fake-grouped{}", env::consts::EXE_SUFFIX);
+        for sub in [&first, &empty, &second] {
+            fs::create_dir(sub).unwrap();
+        }
+        fs::write(first.join(&name), "").unwrap();
+        fs::write(second.join(&name), "").unwrap();
+        let mut second_files = vec![second.join(&name)];
+        if cfg!(windows) {
+            fs::write(second.join("fake-grouped.cmd"), "").unwrap();
+            second_files.push(second.join("fake-grouped.cmd"));
+        }
+
+        let paths = env::join_paths([&first, &empty, &second, &first]).unwrap();
+        let vars = [("PATH", paths.to_str().unwrap()), ("PATHEXT", ".EXE;.CMD")];
+        with_scoped_env(&vars, || {
+            assert_eq!(
+                find_grouped("fake-grouped"),
+                [
+                    (first.clone(), vec![first.join(&name)]),
+                    (second.clone(), second_files)
+                ]
+            );
+            assert!(find_grouped("non-existent-binary").is_empty());
+        });
+    }
+
+    #[test]
+    fn test_find_recorded() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let binary = dir.join(format!("fake-recorded{}", env::consts::EXE_SUFFIX));
+        fs::write(&binary, "").unwrap();
+
+        let vars = [("PATH", dir.as_str()), ("CARGO_HOME", "/cargo-home")];
+        let recorded = with_scoped_env(&vars, || {
+            let recorded = find_recorded("fake-recorded").unwrap();
+            assert_eq!(recorded.path, binary);
+            assert_eq!(recorded.source, Source::Path);
+            assert!(!recorded.is_stale());
+            assert_eq!(find_recorded("non-existent-binary"), None);
+            recorded
+        });
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&recorded).unwrap();
+            assert_eq!(
+                serde_json::from_str::<ResolvedInvocation>(&json).unwrap(),
+                recorded
+            );
+        }
+        let changes: [&[(&str, &str)]; 3] = [
+            &[("PATH", dir.as_str()), ("CARGO_HOME", "/other-cargo-home")],
+            &[("PATH", ""), ("CARGO_HOME", "/cargo-home")],
+            &[
+                ("PATH", dir.as_str()),
+                ("CARGO_HOME", "/cargo-home"),
+                ("FAKE-RECORDED", ""),
+            ],
+        ];
+        for vars in changes {
+            with_scoped_env(vars, || assert!(recorded.is_stale()));
+        }
+    }
+
+    #[test]
+    fn test_path_diff() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let [shared, terminal, gui] = [dir.join("shared"), dir.join("terminal"), dir.join("gui")];
+        let current = env::join_paths([&terminal, &shared]).unwrap();
+        let other =
+            env::join_paths([shared.join("..").join("shared"), gui.clone(), gui.join("")]).unwrap();
+
+        with_scoped_env(&[("PATH", current.to_str().unwrap())], || {
+            assert_eq!(
+                path_diff(other.to_str().unwrap()),
+                PathDiff {
+                    only_in_current: vec![terminal.clone()],
+                    only_in_other: vec![gui.clone()],
+                }
+            );
+            assert_eq!(path_diff(current.to_str().unwrap()), PathDiff::default());
+        });
+    }
+
+    #[test]
+    fn test_find_with_cargo_home() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let fake_cargo_home = temp_dir.path().join(".cargo");
+        fs::create_dir_all(fake_cargo_home.join("bin")).unwrap();
+        let fake_bin = fake_cargo_home.join("bin").join("fake-cargo-binary");
+        fs::write(&fake_bin, "").unwrap();
+
+        env::set_var("CARGO_HOME", fake_cargo_home);
+
+        assert!(find_with_cargo_home("fake-cargo-binary").is_some());
+        assert!(find_with_cargo_home("non-existent-binary").is_none());
+
+        env::remove_var("CARGO_HOME");
+    }
+
+    #[test]
+    fn test_cargo_home_source() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let cargo_home = dir.join("cargo");
+
+        with_scoped_env(&[("CARGO_HOME", cargo_home.as_str())], || {
+            assert_eq!(
+                cargo_home_source(),
+                Some((cargo_home.clone(), CargoHomeSource::EnvVar))
+            );
+        });
+        with_scoped_env(&[("CARGO_HOME", ""), ("HOME", dir.as_str())], || {
+            // Restored by the scope above.
+            env::remove_var("CARGO_HOME");
+            let (path, source) = cargo_home_source().unwrap();
+            assert_eq!(source, CargoHomeSource::DefaultHome);
+            #[cfg(unix)]
+            assert_eq!(path, dir.join(".cargo"));
+            #[cfg(not(unix))]
+            assert_eq!(path.file_name(), Some(".cargo"));
+        });
+    }
+
+    #[test]
+    fn test_find_prefer_dir() {
+        let _guard = lock_env();
+        let preferred = TempDir::new().unwrap();
+        let fake_bin = preferred.path().join("fake-preferred-binary");
+        fs::write(&fake_bin, "").unwrap();
+        let preferred = Utf8Path::from_path(preferred.path()).unwrap();
+
+        assert_eq!(
+            find_prefer_dir(preferred, "fake-preferred-binary"),
+            Some(Utf8PathBuf::try_from(fake_bin).unwrap())
+        );
+
+        with_scoped_env(
+            &[("FAKEFALLBACKBINARY", "/path/to/fakefallbackbinary")],
+            || {
+                assert_eq!(
+                    find_prefer_dir(preferred, "fakefallbackbinary"),
+                    Some(Utf8PathBuf::from("/path/to/fakefallbackbinary"))
+                );
+            },
+        );
+
+        assert_eq!(find_prefer_dir(preferred, "non-existent-binary"), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        let temp_dir = TempDir::new().unwrap();
+        let fake_bin = Utf8PathBuf::try_from(temp_dir.path().join("fake-binary")).unwrap();
+        fs::write(&fake_bin, "").unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            assert_eq!(validate(&fake_bin), None);
+            fs::set_permissions(&fake_bin, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        assert_eq!(validate(&fake_bin), Some(fake_bin.clone()));
+        assert_eq!(validate(&fake_bin.with_file_name("non-existent")), None);
+    }
+
+    #[test]
+    fn test_probe_for_binary() {
+        let temp_dir = TempDir::new().unwrap();
+        let fake_bin = temp_dir.path().join("fake-binary");
+        fs::write(&fake_bin, "").unwrap();
+
+        assert!(probe_for_binary(Utf8PathBuf::try_from(fake_bin).unwrap()).is_some());
+        assert!(probe_for_binary(
+            Utf8PathBuf::try_from(temp_dir.path().join("non-existent")).unwrap()
+        )
+        .is_none());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_probe_for_binary_prefers_pathext() {
+        let temp_dir = TempDir::new().unwrap();
+        let fake_bin = Utf8PathBuf::try_from(temp_dir.path().join("cargo")).unwrap();
+        fs::write(&fake_bin, "").unwrap();
+        fs::write(fake_bin.with_extension("exe"), "").unwrap();
+
+        assert_eq!(
+            probe_for_binary(fake_bin.clone()),
+            Some(fake_bin.with_extension("exe"))
+        );
+        assert_eq!(
+            probe_for_binary(fake_bin.with_extension("exe")),
+            Some(fake_bin.with_extension("exe"))
+        );
     }
 }
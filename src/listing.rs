@@ -0,0 +1,547 @@
+//! Searches that list the contents of PATH directories.
+
+use std::{collections::BTreeSet, io};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::path_dirs;
+
+/// find_by_stem return the first executable on PATH named `stem`, or `stem` followed by a
+/// separator or version, e.g. `ffmpeg-6` or `python3` for the stems `ffmpeg` and `python`.
+///
+/// The matching rule, applied to file names with the executable extension stripped on Windows:
+/// the name equals `stem`, or it starts with `stem` and the next character is `-`, `_`, `.` or an
+/// ASCII digit. Within a directory an exact match wins, then names are taken in sorted order.
+/// Directories are searched in PATH order and their contents listed, which is slower than
+/// [`find`](crate::find). Only executables are matched (the execute bit on Unix, a `PATHEXT`
+/// extension on Windows). A directory that can't be read is skipped like a missing one, see
+/// [`find_by_stem_strict`] to detect it.
+pub fn find_by_stem(stem: &str) -> Option<Utf8PathBuf> {
+    stem_match(stem, false).unwrap_or_default()
+}
+
+/// find_by_stem_strict return the same as [`find_by_stem`], but error out on the first PATH
+/// directory searched that exists and can't be read, like [`list_all_strict`].
+pub fn find_by_stem_strict(stem: &str) -> io::Result<Option<Utf8PathBuf>> {
+    stem_match(stem, true)
+}
+
+fn stem_match(stem: &str, strict: bool) -> io::Result<Option<Utf8PathBuf>> {
+    for dir in path_dirs() {
+        let executables = list_path_dir(&dir, strict)?;
+        let found = executables
+            .iter()
+            .find(|(name, _)| name == stem)
+            .or_else(|| {
+                executables
+                    .iter()
+                    .find(|(name, _)| matches_stem(name, stem))
+            });
+        if let Some((_, path)) = found {
+            return Ok(Some(path.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// list_all return the name of every executable on PATH, deduplicated and sorted, e.g. for a
+/// command listing or completion.
+///
+/// Executables follow the same rules as [`find_by_stem`] (the execute bit on Unix, a `PATHEXT`
+/// extension on Windows, which is stripped from the name). Every PATH directory is listed, which
+/// costs a directory read per entry and a stat per file; directories that can't be read are
+/// skipped, see [`list_all_strict`] to detect them.
+pub fn list_all() -> Vec<String> {
+    list_path_executables(false).unwrap_or_default()
+}
+
+/// list_all_strict return the same names as [`list_all`], but error out on the first PATH
+/// directory that exists and can't be read (e.g. `EACCES`), so an unreadable directory doesn't
+/// silently truncate the listing.
+///
+/// PATH entries that don't exist are still skipped, as they are common and hold nothing to list.
+/// The error message names the offending directory.
+pub fn list_all_strict() -> io::Result<Vec<String>> {
+    list_path_executables(true)
+}
+
+fn list_path_executables(strict: bool) -> io::Result<Vec<String>> {
+    let mut names = BTreeSet::new();
+    for dir in path_dirs() {
+        names.extend(
+            list_path_dir(&dir, strict)?
+                .into_iter()
+                .map(|(name, _)| name),
+        );
+    }
+    Ok(names.into_iter().collect())
+}
+
+// The executables of a PATH directory, see `tolerate` for how read errors are handled.
+fn list_path_dir(dir: &Utf8Path, strict: bool) -> io::Result<Vec<(String, Utf8PathBuf)>> {
+    tolerate(dir, list_executables(dir), strict)
+}
+
+// The read error policy shared by the searches that list directories: a missing directory holds
+// nothing, and so does one that can't be read unless `strict`, in which case the error is
+// returned with a message naming the directory.
+pub(crate) fn tolerate<T: Default>(
+    dir: &Utf8Path,
+    listed: io::Result<T>,
+    strict: bool,
+) -> io::Result<T> {
+    match listed {
+        Err(err) if strict && err.kind() != io::ErrorKind::NotFound => Err(io::Error::new(
+            err.kind(),
+            format!("failed to read {dir}: {err}"),
+        )),
+        Err(_) => Ok(T::default()),
+        listed => listed,
+    }
+}
+
+/// find_glob return every executable on PATH whose name matches `pattern`, e.g. `myapp-plugin-*`
+/// for a plugin loader.
+///
+/// `*` matches any run of characters and `?` any single character; every other character matches
+/// itself. The pattern applies to the file name only (with the executable extension stripped on
+/// Windows), never to directories. Executables follow the same rules as [`find_by_stem`]. Matches
+/// are deduplicated by name, the first directory in PATH order winning, and come in PATH order,
+/// sorted by name within a directory. Directories that can't be read are skipped, see
+/// [`find_glob_strict`] to detect them.
+pub fn find_glob(pattern: &str) -> Vec<Utf8PathBuf> {
+    glob_matches(pattern, false).unwrap_or_default()
+}
+
+/// find_glob_strict return the same matches as [`find_glob`], but error out on the first PATH
+/// directory that exists and can't be read, like [`list_all_strict`].
+pub fn find_glob_strict(pattern: &str) -> io::Result<Vec<Utf8PathBuf>> {
+    glob_matches(pattern, true)
+}
+
+fn glob_matches(pattern: &str, strict: bool) -> io::Result<Vec<Utf8PathBuf>> {
+    let mut names = BTreeSet::new();
+    let mut matches = Vec::new();
+    for dir in path_dirs() {
+        matches.extend(
+            list_path_dir(&dir, strict)?
+                .into_iter()
+                .filter(|(name, _)| matches_glob(name, pattern) && names.insert(name.clone()))
+                .map(|(_, path)| path),
+        );
+    }
+    Ok(matches)
+}
+
+/// find_with_version_fallback return the same as [`find`](crate::find), but on a miss fall back to
+/// the highest versioned variant `<exec><sep><version>` on PATH, e.g. `python3.11` when only that
+/// exists for `python3`.
+///
+/// The separator is `.` or `-`, and the version is one or more dot-separated numbers, so
+/// `python3.11`, `gcc-13` and `clang-17.0.1` are variants but `python3-config` isn't. Versions
+/// compare numerically component by component (`3.9` < `3.11`), a version that is a prefix of
+/// another being lower (`13` < `13.2`); on a tie the first directory in PATH order wins. The
+/// fallback only runs on a direct miss, and then lists every PATH directory like [`find_by_stem`],
+/// skipping the ones that can't be read; see [`find_with_version_fallback_strict`] to detect them.
+pub fn find_with_version_fallback(exec: &str) -> Option<Utf8PathBuf> {
+    version_fallback(exec, false).unwrap_or_default()
+}
+
+/// find_with_version_fallback_strict return the same as [`find_with_version_fallback`], but the
+/// fallback errors out on the first PATH directory that exists and can't be read, like
+/// [`list_all_strict`].
+pub fn find_with_version_fallback_strict(exec: &str) -> io::Result<Option<Utf8PathBuf>> {
+    version_fallback(exec, true)
+}
+
+fn version_fallback(exec: &str, strict: bool) -> io::Result<Option<Utf8PathBuf>> {
+    let exec = crate::trim_exec(exec);
+    if let Some(found) = crate::find(exec) {
+        return Ok(Some(found));
+    }
+    let mut best: Option<(Vec<u64>, Utf8PathBuf)> = None;
+    for dir in path_dirs() {
+        for (name, path) in list_path_dir(&dir, strict)? {
+            let Some(version) = variant_version(&name, exec) else {
+                continue;
+            };
+            if best.as_ref().is_none_or(|(highest, _)| version > *highest) {
+                best = Some((version, path));
+            }
+        }
+    }
+    Ok(best.map(|(_, path)| path))
+}
+
+// The version of `name` if it is `exec` followed by `.` or `-` and dot-separated numbers.
+fn variant_version(name: &str, exec: &str) -> Option<Vec<u64>> {
+    let version = name.strip_prefix(exec)?.strip_prefix(['.', '-'])?;
+    version
+        .split('.')
+        .map(|it| {
+            let is_number = !it.is_empty() && it.bytes().all(|b| b.is_ascii_digit());
+            is_number.then(|| it.parse().ok())?
+        })
+        .collect()
+}
+
+/// find_in_dir_glob return the first match for `exec` in the directories matching `pattern`, e.g.
+/// `/opt/*/bin` for vendored SDKs each installed under their own `/opt` subdirectory.
+///
+/// `pattern` is a directory path whose components may use the wildcards of [`find_glob`]; it is
+/// expanded against the filesystem, listing the directories at each wildcard component in sorted
+/// order, and the resulting directories are probed in that order. The executable name itself is
+/// never globbed. Matches must be executable, as checked by [`validate`](crate::validate). A
+/// directory that can't be listed while expanding a wildcard is skipped, so an unreadable `/opt`
+/// yields no match; see [`find_in_dir_glob_strict`] to detect it.
+pub fn find_in_dir_glob(pattern: &str, exec: &str) -> Option<Utf8PathBuf> {
+    dir_glob_match(pattern, exec, false).unwrap_or_default()
+}
+
+/// find_in_dir_glob_strict return the same as [`find_in_dir_glob`], but error out on the first
+/// directory that exists and can't be listed while expanding `pattern`, like [`list_all_strict`].
+pub fn find_in_dir_glob_strict(pattern: &str, exec: &str) -> io::Result<Option<Utf8PathBuf>> {
+    dir_glob_match(pattern, exec, true)
+}
+
+fn dir_glob_match(pattern: &str, exec: &str, strict: bool) -> io::Result<Option<Utf8PathBuf>> {
+    let exec = crate::trim_exec(exec);
+    Ok(expand_dir_glob(pattern, strict)?
+        .into_iter()
+        .find_map(|dir| crate::validate(&dir.join(exec))))
+}
+
+fn expand_dir_glob(pattern: &str, strict: bool) -> io::Result<Vec<Utf8PathBuf>> {
+    let mut dirs = vec![Utf8PathBuf::new()];
+    for component in Utf8Path::new(pattern).components() {
+        let part = component.as_str();
+        if !part.contains(['*', '?']) {
+            dirs.iter_mut().for_each(|dir| dir.push(part));
+            continue;
+        }
+        let mut expanded = Vec::new();
+        for dir in dirs {
+            let listed = if dir.as_str().is_empty() {
+                Utf8Path::new(".")
+            } else {
+                &dir
+            };
+            // A literal component naming a file has nothing to list.
+            if !listed.is_dir() {
+                continue;
+            }
+            let entries = listed.read_dir_utf8().map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .filter(|entry| entry.path().is_dir() && matches_glob(entry.file_name(), part))
+                    .map(|entry| entry.file_name().to_owned())
+                    .collect::<Vec<_>>()
+            });
+            let mut names = tolerate(listed, entries, strict)?;
+            names.sort();
+            expanded.extend(names.into_iter().map(|name| dir.join(name)));
+        }
+        dirs = expanded;
+    }
+    dirs.retain(|dir| dir.is_dir());
+    Ok(dirs)
+}
+
+fn matches_glob(name: &str, pattern: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    // Iterative matching with backtracking to the last `*`.
+    let (mut n, mut p) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                n += 1;
+                p += 1;
+            }
+            _ => match backtrack {
+                Some((star, start)) => {
+                    p = star + 1;
+                    n = start + 1;
+                    backtrack = Some((star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn matches_stem(name: &str, stem: &str) -> bool {
+    name.strip_prefix(stem)
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(|c| matches!(c, '-' | '_' | '.') || c.is_ascii_digit())
+}
+
+// The executables in `dir` as `(name, path)` pairs sorted by name, the executable extension being
+// stripped from the name on Windows.
+fn list_executables(dir: &Utf8Path) -> io::Result<Vec<(String, Utf8PathBuf)>> {
+    let mut executables: Vec<_> = dir
+        .read_dir_utf8()?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.into_path();
+            let name = executable_name(&path)?;
+            Some((name, path))
+        })
+        .collect();
+    executables.sort();
+    Ok(executables)
+}
+
+#[cfg(windows)]
+fn executable_name(path: &Utf8Path) -> Option<String> {
+    let extension = path.extension()?;
+    let is_executable = crate::path_extensions()
+        .iter()
+        .any(|ext| ext.eq_ignore_ascii_case(extension));
+    (is_executable && path.is_file()).then(|| path.file_stem().map(str::to_owned))?
+}
+
+#[cfg(not(windows))]
+fn executable_name(path: &Utf8Path) -> Option<String> {
+    crate::is_executable(path).then(|| path.file_name().map(str::to_owned))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use std::fs;
+    use tempfile::TempDir;
+
+    // Creates an executable named `name` in `dir`, adding `.exe` on Windows.
+    fn write_executable(dir: &Utf8Path, name: &str) -> Utf8PathBuf {
+        let path = if cfg!(windows) {
+            dir.join(format!("{name}.exe"))
+        } else {
+            dir.join(name)
+        };
+        fs::write(&path, "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn test_matches_stem() {
+        assert!(matches_stem("ffmpeg-6", "ffmpeg"));
+        assert!(matches_stem("python3", "python"));
+        assert!(matches_stem("tool_v2", "tool"));
+        assert!(!matches_stem("ffmpeg", "ffmpeg"));
+        assert!(!matches_stem("ffmpegthumbnailer", "ffmpeg"));
+    }
+
+    #[test]
+    fn test_matches_glob() {
+        assert!(matches_glob("myapp-plugin-foo", "myapp-plugin-*"));
+        assert!(matches_glob("myapp-plugin-", "myapp-plugin-*"));
+        assert!(matches_glob("tool1", "tool?"));
+        assert!(matches_glob("a-b-c", "*-*-c"));
+        assert!(!matches_glob("tool12", "tool?"));
+        assert!(!matches_glob("myapp-plugin", "myapp-plugin-*"));
+        assert!(!matches_glob("a-b-d", "*-*-c"));
+    }
+
+    #[test]
+    fn test_variant_version() {
+        assert_eq!(variant_version("python3.11", "python3"), Some(vec![11]));
+        assert_eq!(
+            variant_version("clang-17.0.1", "clang"),
+            Some(vec![17, 0, 1])
+        );
+        assert_eq!(variant_version("python3-config", "python3"), None);
+        assert_eq!(variant_version("gcc-+1", "gcc"), None);
+        assert_eq!(variant_version("gcc-13.", "gcc"), None);
+        assert_eq!(variant_version("gcc13", "gcc"), None);
+    }
+
+    #[test]
+    fn test_find_with_version_fallback() {
+        let _guard = lock_env();
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let first = Utf8PathBuf::try_from(first.path().to_path_buf()).unwrap();
+        let second = Utf8PathBuf::try_from(second.path().to_path_buf()).unwrap();
+        write_executable(&first, "fake-python3.9");
+        let highest = write_executable(&second, "fake-python3.11");
+        write_executable(&second, "fake-python3-config");
+        let exact = write_executable(&second, "fake-gcc");
+        write_executable(&first, "fake-gcc-13");
+
+        let paths = std::env::join_paths([&first, &second]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(find_with_version_fallback("fake-python3"), Some(highest));
+            assert_eq!(find_with_version_fallback("fake-gcc"), Some(exact));
+            assert_eq!(find_with_version_fallback("non-existent-binary"), None);
+        });
+    }
+
+    #[test]
+    fn test_find_glob() {
+        let _guard = lock_env();
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let first = Utf8PathBuf::try_from(first.path().to_path_buf()).unwrap();
+        let second = Utf8PathBuf::try_from(second.path().to_path_buf()).unwrap();
+        let b = write_executable(&first, "fake-plugin-b");
+        let a = write_executable(&second, "fake-plugin-a");
+        write_executable(&second, "fake-plugin-b");
+        write_executable(&second, "fake-other");
+
+        let paths = std::env::join_paths([&first, &second]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(find_glob("fake-plugin-*"), [b, a]);
+            assert!(find_glob("fake-plugin-?z").is_empty());
+        });
+    }
+
+    #[test]
+    fn test_find_in_dir_glob() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        for sdk in ["sdk-a", "sdk-b", "sdk-c", "other"] {
+            fs::create_dir_all(dir.join(sdk).join("bin")).unwrap();
+        }
+        // Not executable, so skipped in favor of the next directory.
+        #[cfg(unix)]
+        fs::write(dir.join("sdk-a/bin/fake-sdk-tool"), "").unwrap();
+        let expected = write_executable(&dir.join("sdk-b/bin"), "fake-sdk-tool");
+        write_executable(&dir.join("sdk-c/bin"), "fake-sdk-tool");
+        let other = write_executable(&dir.join("other/bin"), "fake-other-tool");
+
+        let pattern = dir.join("sdk-*").join("bin");
+        assert_eq!(
+            find_in_dir_glob(pattern.as_str(), "fake-sdk-tool"),
+            Some(expected)
+        );
+        assert_eq!(find_in_dir_glob(pattern.as_str(), "fake-other-tool"), None);
+        assert_eq!(
+            find_in_dir_glob(dir.join("*/bin").as_str(), "fake-other-tool"),
+            Some(other)
+        );
+    }
+
+    #[test]
+    fn test_find_by_stem() {
+        let _guard = lock_env();
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let first = Utf8PathBuf::try_from(first.path().to_path_buf()).unwrap();
+        let second = Utf8PathBuf::try_from(second.path().to_path_buf()).unwrap();
+        let versioned = write_executable(&first, "fake-stem-6");
+        write_executable(&first, "fake-stemmed");
+        let exact = write_executable(&second, "fake-stem");
+        let other = write_executable(&second, "fake-other-1");
+        // Not executable, so skipped in favor of the loose match in the next directory.
+        fs::write(first.join("fake-other"), "").unwrap();
+
+        let paths = std::env::join_paths([&first, &second]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(find_by_stem("fake-stem"), Some(versioned));
+            assert_eq!(find_by_stem("non-existent-stem"), None);
+            assert_eq!(find_by_stem("fake-other"), Some(other));
+        });
+        let paths = std::env::join_paths([&second, &first]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(find_by_stem("fake-stem"), Some(exact));
+        });
+    }
+
+    #[test]
+    fn test_list_all() {
+        let _guard = lock_env();
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let first = Utf8PathBuf::try_from(first.path().to_path_buf()).unwrap();
+        let second = Utf8PathBuf::try_from(second.path().to_path_buf()).unwrap();
+        write_executable(&first, "fake-list-b");
+        write_executable(&second, "fake-list-b");
+        write_executable(&second, "fake-list-a");
+        fs::write(first.join("fake-list-c"), "").unwrap();
+
+        let paths = std::env::join_paths([&first, &second]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(list_all(), ["fake-list-a", "fake-list-b"]);
+        });
+    }
+
+    #[test]
+    fn test_list_all_strict() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        write_executable(&dir, "fake-strict");
+        // A PATH entry that exists but can't be listed, even when running as root.
+        let not_a_dir = dir.join("not-a-dir");
+        fs::write(&not_a_dir, "").unwrap();
+
+        let paths = std::env::join_paths([dir.join("missing"), dir.clone()]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(list_all_strict().unwrap(), ["fake-strict"]);
+        });
+        let paths = std::env::join_paths([dir.clone(), not_a_dir.clone()]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            assert_eq!(list_all(), ["fake-strict"]);
+            let err = list_all_strict().unwrap_err();
+            assert!(err.to_string().contains(not_a_dir.as_str()));
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_strict_listing() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let [locked, open] = [dir.join("locked"), dir.join("open")];
+        fs::create_dir_all(&locked).unwrap();
+        fs::create_dir_all(&open).unwrap();
+        write_executable(&open, "fake-strict-1.2");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // Root can list the directory anyway, so there is no read error to surface.
+        let readable = locked.read_dir_utf8().is_ok();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        if readable {
+            return;
+        }
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let paths = std::env::join_paths([&locked, &open]).unwrap();
+        with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            let found = Some(open.join("fake-strict-1.2"));
+            assert_eq!(find_by_stem("fake-strict"), found);
+            assert_eq!(find_glob("fake-strict-*"), [open.join("fake-strict-1.2")]);
+            assert_eq!(find_with_version_fallback("fake-strict"), found);
+            assert_eq!(
+                find_in_dir_glob(&format!("{dir}/*"), "fake-strict-1.2"),
+                found
+            );
+
+            let failed_on_locked = |err: io::Error| err.to_string().contains(locked.as_str());
+            assert!(find_by_stem_strict("fake-strict").is_err_and(failed_on_locked));
+            assert!(find_glob_strict("fake-strict-*").is_err_and(failed_on_locked));
+            assert!(find_with_version_fallback_strict("fake-strict").is_err_and(failed_on_locked));
+            let pattern = format!("{dir}/*/*");
+            assert!(
+                find_in_dir_glob_strict(&pattern, "fake-strict-1.2").is_err_and(failed_on_locked)
+            );
+        });
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
@@ -0,0 +1,67 @@
+//! Searching the PATH of the user's login shell.
+
+use std::{env, process::Command};
+
+use camino::Utf8PathBuf;
+
+use crate::{probe_for_binary, trim_exec};
+
+// Delimits PATH in the shell output, as profile scripts may print their own messages.
+const MARKER: &str = "__EXECUTABLE_PATH_FINDER_PATH__";
+
+/// find_in_login_shell_path return the first match for `exec` in the PATH of the user's login
+/// shell, for GUI apps started with a minimal environment ("works in the terminal, not when
+/// launched from the Dock").
+///
+/// `$SHELL` (or `/bin/sh` when unset) is spawned in login mode (`-l -c`) to print its PATH, which
+/// is then searched like [`find_in_path`](crate::find_in_path). Returns `None` if the shell fails
+/// to run or exits unsuccessfully.
+///
+/// The shell must accept `-l` together with `-c`, as POSIX-style shells (`sh`, `bash`, `zsh`, ...)
+/// do. csh and tcsh only accept `-l` as their sole argument, so for users of those shells this
+/// always returns `None`.
+///
+/// This runs the user's profile scripts, with their side effects, and only makes sense for the
+/// current user's own environment. Spawning a shell also takes from milliseconds to seconds
+/// depending on the profile, so callers should cache the result rather than calling this on a
+/// hot path.
+pub fn find_in_login_shell_path(exec: &str) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    let shell = env::var_os("SHELL")
+        .filter(|it| !it.is_empty())
+        .unwrap_or_else(|| "/bin/sh".into());
+    let output = Command::new(shell)
+        .arg("-l")
+        .arg("-c")
+        .arg(format!("printf '{MARKER}%s{MARKER}' \"$PATH\""))
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let (_, rest) = stdout.split_once(MARKER)?;
+    let (paths, _) = rest.split_once(MARKER)?;
+    env::split_paths(paths)
+        .filter_map(|dir| Utf8PathBuf::try_from(dir).ok())
+        .find_map(|dir| probe_for_binary(dir.join(exec)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+
+    #[test]
+    fn test_find_in_login_shell_path() {
+        let _guard = lock_env();
+        with_scoped_env(&[("SHELL", "/bin/sh"), ("PATH", "/usr/bin:/bin")], || {
+            assert!(find_in_login_shell_path("sh").is_some());
+            assert_eq!(find_in_login_shell_path("non-existent-binary"), None);
+        });
+        with_scoped_env(&[("SHELL", "/non-existent/shell")], || {
+            assert_eq!(find_in_login_shell_path("sh"), None);
+        });
+    }
+}
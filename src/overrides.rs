@@ -0,0 +1,62 @@
+//! Stubbing lookups in the tests of downstream crates.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use camino::Utf8PathBuf;
+
+use crate::trim_exec;
+
+static OVERRIDES: Mutex<BTreeMap<String, Option<Utf8PathBuf>>> = Mutex::new(BTreeMap::new());
+
+/// override_find make [`find`](crate::find) and
+/// [`find_with_cargo_home`](crate::find_with_cargo_home) return `path` for `exec` without touching
+/// the filesystem or the environment; `None` makes `exec` not found.
+///
+/// Overrides are process-global: they apply to every thread until removed with
+/// [`clear_overrides`], so tests running in parallel see each other's overrides. This is intended
+/// for tests only, hence the `test-util` feature.
+pub fn override_find(exec: &str, path: Option<Utf8PathBuf>) {
+    lock().insert(trim_exec(exec).to_owned(), path);
+}
+
+/// clear_overrides remove every override installed with [`override_find`].
+pub fn clear_overrides() {
+    lock().clear();
+}
+
+pub(crate) fn lookup(exec: &str) -> Option<Option<Utf8PathBuf>> {
+    lock().get(trim_exec(exec)).cloned()
+}
+
+fn lock() -> MutexGuard<'static, BTreeMap<String, Option<Utf8PathBuf>>> {
+    // The map is never left half-updated, so a panic while holding the lock is harmless.
+    OVERRIDES.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{find, find_with_cargo_home, tests::lock_env, with_scoped_env};
+
+    #[test]
+    fn test_override_find() {
+        let _guard = lock_env();
+        with_scoped_env(&[("FAKE-OVERRIDDEN", "/path/to/env")], || {
+            override_find("fake-overridden", Some("/path/to/override".into()));
+            assert_eq!(find("fake-overridden"), Some("/path/to/override".into()));
+            assert_eq!(
+                find_with_cargo_home("fake-overridden/"),
+                Some("/path/to/override".into())
+            );
+
+            override_find("fake-overridden", None);
+            assert_eq!(find("fake-overridden"), None);
+
+            clear_overrides();
+            assert_eq!(find("fake-overridden"), Some("/path/to/env".into()));
+        });
+    }
+}
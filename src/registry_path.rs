@@ -0,0 +1,99 @@
+//! Searching the PATH stored in the Windows registry, as opposed to the inherited one.
+
+use std::{env, ffi::OsString, os::windows::ffi::OsStringExt, ptr};
+
+use camino::Utf8PathBuf;
+use windows_sys::Win32::{
+    Foundation::{ERROR_MORE_DATA, ERROR_SUCCESS},
+    System::Registry::{RegGetValueW, HKEY, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ},
+};
+
+use crate::{probe_for_binary, trim_exec};
+
+const MACHINE_ENVIRONMENT: &str = r"SYSTEM\CurrentControlSet\Control\Session Manager\Environment";
+const USER_ENVIRONMENT: &str = "Environment";
+
+/// find_in_registry_path return the first match for `exec` in the PATH currently stored in the
+/// registry, so a tool installed after this process started is found without relaunching it.
+///
+/// The machine PATH (`Path` in `HKEY_LOCAL_MACHINE\SYSTEM\CurrentControlSet\Control\Session
+/// Manager\Environment`) is searched first, then the user PATH (`Path` in
+/// `HKEY_CURRENT_USER\Environment`), the same concatenation Windows uses to build the PATH of new
+/// processes. `%VAR%` references in the values are expanded with this process's environment.
+/// A missing or unreadable value is skipped. The inherited PATH isn't consulted, see
+/// [`find_in_path`](crate::find_in_path); probing uses the same extension logic as PATH entries,
+/// see [`probe_for_binary`].
+pub fn find_in_registry_path(exec: &str) -> Option<Utf8PathBuf> {
+    let exec = trim_exec(exec);
+    let paths: Vec<_> = [
+        (HKEY_LOCAL_MACHINE, MACHINE_ENVIRONMENT),
+        (HKEY_CURRENT_USER, USER_ENVIRONMENT),
+    ]
+    .into_iter()
+    .filter_map(|(key, subkey)| registry_string(key, subkey, "Path"))
+    .collect();
+    paths
+        .iter()
+        .flat_map(env::split_paths)
+        .filter_map(|dir| Utf8PathBuf::try_from(dir).ok())
+        .find_map(|dir| probe_for_binary(dir.join(exec)))
+}
+
+// The string value `name` of `key\subkey`, with `REG_EXPAND_SZ` values expanded.
+fn registry_string(key: HKEY, subkey: &str, name: &str) -> Option<OsString> {
+    let subkey = wide(subkey);
+    let name = wide(name);
+    let mut buf: Vec<u16> = Vec::new();
+    loop {
+        let mut len = (buf.len() * 2) as u32;
+        let data = if buf.is_empty() {
+            ptr::null_mut()
+        } else {
+            buf.as_mut_ptr().cast()
+        };
+        let status = unsafe {
+            RegGetValueW(
+                key,
+                subkey.as_ptr(),
+                name.as_ptr(),
+                RRF_RT_REG_SZ,
+                ptr::null_mut(),
+                data,
+                &mut len,
+            )
+        };
+        match status {
+            // The size query, or the value grew in between: retry with the reported size.
+            ERROR_SUCCESS if buf.is_empty() => buf.resize((len as usize).div_ceil(2).max(1), 0),
+            ERROR_MORE_DATA => buf.resize((len as usize).div_ceil(2), 0),
+            ERROR_SUCCESS => {
+                buf.truncate(len as usize / 2);
+                while buf.last() == Some(&0) {
+                    buf.pop();
+                }
+                return Some(OsString::from_wide(&buf));
+            }
+            _ => return None,
+        }
+    }
+}
+
+fn wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain([0]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_in_registry_path() {
+        // `System32` is on the machine PATH of every Windows installation.
+        assert!(find_in_registry_path("cmd").is_some());
+        assert_eq!(find_in_registry_path("non-existent-binary"), None);
+        assert_eq!(
+            registry_string(HKEY_CURRENT_USER, USER_ENVIRONMENT, "non-existent"),
+            None
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Seeing through rustup proxies.
+
+use std::{env, fs};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{find, get_cargo_home, home, probe_for_binary};
+
+/// resolve_rustup_proxy return the toolchain binary a rustup proxy found by [`find`] would
+/// dispatch to, e.g. `$RUSTUP_HOME/toolchains/stable-x86_64-unknown-linux-gnu/bin/rustc`.
+///
+/// The found binary is a proxy when it is the `rustup` binary under another name: a symlink to
+/// it, or a hardlink sitting next to it as installed by rustup. When it is not a proxy, the found
+/// binary itself is returned.
+///
+/// The active toolchain is selected with the following precedence:
+/// 1) the `RUSTUP_TOOLCHAIN` environment variable
+/// 2) the `channel` of the nearest `rust-toolchain.toml` or `rust-toolchain` file, looking in the
+///    current directory and its ancestors
+/// 3) the `default_toolchain` of `$RUSTUP_HOME/settings.toml`
+///
+/// Directory overrides set with `rustup override` are not consulted. Returns `None` when no
+/// toolchain is active or it doesn't ship the executable.
+pub fn resolve_rustup_proxy(exec: &str) -> Option<Utf8PathBuf> {
+    let found = find(exec)?;
+    if !is_rustup_proxy(&found) {
+        return Some(found);
+    }
+    let rustup_home = Utf8PathBuf::try_from(home::rustup_home().ok()?).ok()?;
+    let settings = fs::read_to_string(rustup_home.join("settings.toml")).unwrap_or_default();
+    let toolchain = active_toolchain(&settings)?;
+    let toolchain_dir = toolchain_dir(&rustup_home.join("toolchains"), &toolchain, &settings)?;
+    probe_for_binary(toolchain_dir.join("bin").join(found.file_name()?))
+}
+
+/// find_with_managed_flag return the executable resolved by [`find`] together with whether it is
+/// managed by rustup or cargo, i.e. whether its canonicalized target lives under CARGO_HOME or
+/// RUSTUP_HOME, as opposed to a standalone or system install.
+///
+/// CARGO_HOME is resolved like for [`find_in_cargo_home`](crate::find_in_cargo_home)
+/// (`$CARGO_HOME`, defaulting to `~/.cargo`) and RUSTUP_HOME like rustup does (`$RUSTUP_HOME`,
+/// defaulting to `~/.rustup`). Symlinks are resolved on both sides, so a `/usr/local/bin/cargo`
+/// symlink into `~/.cargo/bin` counts as managed.
+pub fn find_with_managed_flag(exec: &str) -> Option<(Utf8PathBuf, bool)> {
+    let found = find(exec)?;
+    let target = canonicalize(&found);
+    let rustup_home = home::rustup_home()
+        .ok()
+        .and_then(|it| Utf8PathBuf::try_from(it).ok());
+    let managed = get_cargo_home()
+        .into_iter()
+        .chain(rustup_home)
+        .any(|home| target.starts_with(canonicalize(&home)));
+    Some((found, managed))
+}
+
+fn canonicalize(path: &Utf8Path) -> Utf8PathBuf {
+    path.canonicalize_utf8()
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn is_rustup_proxy(path: &Utf8Path) -> bool {
+    let is_rustup = |it: &Utf8Path| it.file_stem() == Some("rustup");
+    if is_rustup(path) {
+        return false;
+    }
+    if path.canonicalize_utf8().is_ok_and(|it| is_rustup(&it)) {
+        return true;
+    }
+    let Some(rustup) = path
+        .parent()
+        .and_then(|dir| probe_for_binary(dir.join("rustup")))
+    else {
+        return false;
+    };
+    same_file(path, &rustup)
+}
+
+#[cfg(unix)]
+fn same_file(a: &Utf8Path, b: &Utf8Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+// A hardlinked proxy shares the volume serial number and file index of `rustup.exe`.
+#[cfg(windows)]
+fn same_file(a: &Utf8Path, b: &Utf8Path) -> bool {
+    use std::{fs::File, mem::MaybeUninit, os::windows::io::AsRawHandle};
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION,
+    };
+
+    let file_id = |path: &Utf8Path| {
+        let file = File::open(path).ok()?;
+        let mut info = MaybeUninit::<BY_HANDLE_FILE_INFORMATION>::uninit();
+        // SAFETY: the handle is open for the duration of the call, and `info` is only read after
+        // the call reports success.
+        let info = unsafe {
+            if GetFileInformationByHandle(file.as_raw_handle(), info.as_mut_ptr()) == 0 {
+                return None;
+            }
+            info.assume_init()
+        };
+        Some((
+            info.dwVolumeSerialNumber,
+            info.nFileIndexHigh,
+            info.nFileIndexLow,
+        ))
+    };
+    match (file_id(a), file_id(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Elsewhere there is no file identity to compare, so only a symlinked proxy is recognized.
+#[cfg(not(any(unix, windows)))]
+fn same_file(_: &Utf8Path, _: &Utf8Path) -> bool {
+    false
+}
+
+fn active_toolchain(settings: &str) -> Option<String> {
+    if let Some(toolchain) = env::var("RUSTUP_TOOLCHAIN")
+        .ok()
+        .filter(|it| !it.is_empty())
+    {
+        return Some(toolchain);
+    }
+    let cwd = env::current_dir().ok()?;
+    for dir in cwd.ancestors() {
+        if let Ok(contents) = fs::read_to_string(dir.join("rust-toolchain.toml")) {
+            return toml_string_value(&contents, "channel");
+        }
+        if let Ok(contents) = fs::read_to_string(dir.join("rust-toolchain")) {
+            // The legacy file holds either a bare channel name or the TOML format.
+            return toml_string_value(&contents, "channel").or_else(|| {
+                let channel = contents.trim();
+                (!channel.is_empty() && !channel.contains('\n')).then(|| channel.to_owned())
+            });
+        }
+    }
+    toml_string_value(settings, "default_toolchain")
+}
+
+// `stable` is installed as `stable-<host triple>`, so fall back to the host-qualified name.
+fn toolchain_dir(toolchains: &Utf8Path, toolchain: &str, settings: &str) -> Option<Utf8PathBuf> {
+    let exact = toolchains.join(toolchain);
+    if exact.is_dir() {
+        return Some(exact);
+    }
+    if let Some(host) = toml_string_value(settings, "default_host_triple") {
+        let qualified = toolchains.join(format!("{toolchain}-{host}"));
+        if qualified.is_dir() {
+            return Some(qualified);
+        }
+    }
+    let prefix = format!("{toolchain}-");
+    let mut candidates: Vec<Utf8PathBuf> = toolchains
+        .read_dir_utf8()
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().starts_with(&prefix))
+        .map(|entry| entry.into_path())
+        .filter(|path| path.is_dir())
+        .collect();
+    candidates.sort();
+    candidates.into_iter().next()
+}
+
+// Reads `key = "value"` from a flat TOML document, enough for rustup's own files.
+fn toml_string_value(contents: &str, key: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let (name, value) = line.split_once('=')?;
+        if name.trim() != key {
+            return None;
+        }
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        (!value.is_empty()).then(|| value.to_owned())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_string_value() {
+        let settings =
+            "version = \"12\"\ndefault_toolchain = \"stable-x86_64-unknown-linux-gnu\"\n";
+        assert_eq!(
+            toml_string_value(settings, "default_toolchain").as_deref(),
+            Some("stable-x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(toml_string_value(settings, "channel"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rustup_proxy() {
+        use crate::tests::lock_env;
+        use crate::with_scoped_env;
+        use tempfile::TempDir;
+
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let cargo_bin = dir.join("cargo/bin");
+        let toolchain_bin = dir.join("rustup/toolchains/stable-x86_64-unknown-linux-gnu/bin");
+        fs::create_dir_all(&cargo_bin).unwrap();
+        fs::create_dir_all(&toolchain_bin).unwrap();
+        fs::write(cargo_bin.join("rustup"), "rustup").unwrap();
+        fs::hard_link(cargo_bin.join("rustup"), cargo_bin.join("fake-rustc")).unwrap();
+        fs::write(cargo_bin.join("fake-standalone"), "standalone").unwrap();
+        fs::write(toolchain_bin.join("fake-rustc"), "rustc").unwrap();
+        fs::write(
+            dir.join("rustup/settings.toml"),
+            "default_host_triple = \"x86_64-unknown-linux-gnu\"\n",
+        )
+        .unwrap();
+
+        let rustup_home = dir.join("rustup");
+        let vars = [
+            ("PATH", cargo_bin.as_str()),
+            ("RUSTUP_HOME", rustup_home.as_str()),
+            ("RUSTUP_TOOLCHAIN", "stable"),
+        ];
+        with_scoped_env(&vars, || {
+            assert_eq!(
+                resolve_rustup_proxy("fake-rustc"),
+                Some(toolchain_bin.join("fake-rustc"))
+            );
+            assert_eq!(
+                resolve_rustup_proxy("fake-standalone"),
+                Some(cargo_bin.join("fake-standalone"))
+            );
+            assert_eq!(resolve_rustup_proxy("non-existent-binary"), None);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_with_managed_flag() {
+        use crate::tests::lock_env;
+        use crate::with_scoped_env;
+        use tempfile::TempDir;
+
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let cargo_home = dir.join("cargo");
+        let rustup_home = dir.join("rustup");
+        let toolchain_bin = rustup_home.join("toolchains/stable/bin");
+        let system_bin = dir.join("usr/bin");
+        fs::create_dir_all(cargo_home.join("bin")).unwrap();
+        fs::create_dir_all(&toolchain_bin).unwrap();
+        fs::create_dir_all(&system_bin).unwrap();
+        fs::write(cargo_home.join("bin/fake-cargo"), "").unwrap();
+        fs::write(toolchain_bin.join("fake-rustc"), "").unwrap();
+        fs::write(system_bin.join("fake-system-cargo"), "").unwrap();
+        std::os::unix::fs::symlink(
+            cargo_home.join("bin/fake-cargo"),
+            system_bin.join("fake-cargo"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(
+            toolchain_bin.join("fake-rustc"),
+            system_bin.join("fake-rustc"),
+        )
+        .unwrap();
+
+        let vars = [
+            ("PATH", system_bin.as_str()),
+            ("CARGO_HOME", cargo_home.as_str()),
+            ("RUSTUP_HOME", rustup_home.as_str()),
+        ];
+        with_scoped_env(&vars, || {
+            assert_eq!(
+                find_with_managed_flag("fake-cargo"),
+                Some((system_bin.join("fake-cargo"), true))
+            );
+            assert_eq!(
+                find_with_managed_flag("fake-rustc"),
+                Some((system_bin.join("fake-rustc"), true))
+            );
+            assert_eq!(
+                find_with_managed_flag("fake-system-cargo"),
+                Some((system_bin.join("fake-system-cargo"), false))
+            );
+            assert_eq!(find_with_managed_flag("non-existent-binary"), None);
+        });
+    }
+}
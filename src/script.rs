@@ -0,0 +1,186 @@
+//! Telling which interpreter a script found on PATH runs with.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::find;
+
+// Longer shebang lines are truncated by the kernel anyway.
+const MAX_SHEBANG_LEN: u64 = 256;
+
+// How many interpreters can be nested, the limit of the Linux kernel (`BINPRM_MAX_RECURSION`).
+const MAX_INTERPRETER_DEPTH: usize = 4;
+
+/// The interpreter language of a script, as told by [`script_kind`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptKind {
+    Python,
+    Node,
+    /// A POSIX-style shell such as `sh`, `bash` or `zsh`.
+    Shell,
+    /// Any other interpreter, given by the file name of its path.
+    Other(String),
+}
+
+/// script_kind return the interpreter language of the script at `path` by parsing its shebang line
+/// (`#!/usr/bin/python3`, `#!/usr/bin/env node`, ...), e.g. to decide how to invoke a tool.
+///
+/// Only the first line of the file is read. With `env`, the first argument that isn't an option
+/// is the interpreter. Returns `None` for a file without a shebang, such as a native binary, and
+/// for a file that can't be read.
+pub fn script_kind(path: &Utf8Path) -> Option<ScriptKind> {
+    Some(classify(file_name(&interpreter(path)?)))
+}
+
+/// resolve_exec_chain return the chain of files run for `exec`: the match of [`find`], then the
+/// interpreter of its shebang line, then that interpreter's own interpreter and so on, down to
+/// the native binary, e.g. for a launcher that execs the interpreter directly.
+///
+/// An interpreter given as a path is used as-is and must exist; a bare name, and the program run
+/// through `#!/usr/bin/env`, are resolved with [`find`] (`env` itself is left out of the chain).
+/// A native binary, or any file without a shebang, yields a single-element chain. Returns `None`
+/// when `exec` isn't found, an interpreter can't be resolved, the chain loops back to a file
+/// already in it, or more than four interpreters are nested (the Linux kernel's own limit).
+pub fn resolve_exec_chain(exec: &str) -> Option<Vec<Utf8PathBuf>> {
+    let mut chain = vec![find(exec)?];
+    while let Some(interpreter) = interpreter(chain.last()?) {
+        let next = if interpreter.contains(['/', '\\']) {
+            Some(Utf8PathBuf::from(interpreter)).filter(|it| it.is_file())?
+        } else {
+            find(&interpreter)?
+        };
+        if chain.contains(&next) || chain.len() > MAX_INTERPRETER_DEPTH {
+            return None;
+        }
+        chain.push(next);
+    }
+    Some(chain)
+}
+
+// The interpreter in the shebang line of `path`: the path as written, or the program run through
+// `env`.
+fn interpreter(path: &Utf8Path) -> Option<String> {
+    let mut line = Vec::new();
+    BufReader::new(File::open(path).ok()?.take(MAX_SHEBANG_LEN))
+        .read_until(b'\n', &mut line)
+        .ok()?;
+    let line = String::from_utf8_lossy(line.strip_prefix(b"#!")?);
+    let mut words = line.split_whitespace();
+    let interpreter = words.next()?;
+    if file_name(interpreter) == "env" {
+        return env_program(words).map(str::to_owned);
+    }
+    Some(interpreter.to_owned())
+}
+
+// The program `env` runs given its arguments: the first word that is neither an option, the
+// argument of `-u`/`-C`, nor a `NAME=value` assignment.
+fn env_program<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    while let Some(word) = words.next() {
+        match word {
+            "-u" | "-C" | "--unset" | "--chdir" => {
+                words.next();
+            }
+            it if it.starts_with('-') || it.contains('=') => {}
+            it => return Some(it),
+        }
+    }
+    None
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+fn classify(interpreter: &str) -> ScriptKind {
+    match interpreter {
+        it if it.starts_with("python") => ScriptKind::Python,
+        "node" | "nodejs" => ScriptKind::Node,
+        "sh" | "bash" | "dash" | "zsh" | "ksh" | "ash" => ScriptKind::Shell,
+        it => ScriptKind::Other(it.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_script_kind() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let kind = |contents: &[u8]| {
+            fs::write(dir.join("script"), contents).unwrap();
+            script_kind(&dir.join("script"))
+        };
+
+        assert_eq!(kind(b"#!/usr/bin/python3.11\n"), Some(ScriptKind::Python));
+        assert_eq!(
+            kind(b"#!/usr/bin/env node\nconsole.log(1)\n"),
+            Some(ScriptKind::Node)
+        );
+        assert_eq!(
+            kind(b"#!/usr/bin/env -S bash -e\n"),
+            Some(ScriptKind::Shell)
+        );
+        assert_eq!(kind(b"#! /bin/sh"), Some(ScriptKind::Shell));
+        assert_eq!(
+            kind(b"#!/usr/bin/env -S FOO=1 python3\n"),
+            Some(ScriptKind::Python)
+        );
+        assert_eq!(
+            kind(b"#!/usr/bin/env -u NAME node\n"),
+            Some(ScriptKind::Node)
+        );
+        assert_eq!(
+            kind(b"#!/usr/bin/env -C /tmp --unset NAME bash\n"),
+            Some(ScriptKind::Shell)
+        );
+        assert_eq!(
+            kind(b"#!/usr/bin/ruby -w\n"),
+            Some(ScriptKind::Other("ruby".to_owned()))
+        );
+        assert_eq!(kind(b"\x7fELF\x02\x01\x01"), None);
+        assert_eq!(kind(b"#!\n"), None);
+        assert_eq!(script_kind(&dir.join("missing")), None);
+    }
+
+    #[test]
+    fn test_resolve_exec_chain() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let native = dir.join("fake-native");
+        fs::write(&native, b"\x7fELF\x02\x01\x01").unwrap();
+        fs::write(dir.join("fake-interpreter"), format!("#!{native} -x\n")).unwrap();
+        fs::write(dir.join("fake-script"), "#!/usr/bin/env fake-interpreter\n").unwrap();
+        fs::write(dir.join("fake-loop"), "#!/usr/bin/env fake-loop\n").unwrap();
+        fs::write(dir.join("fake-broken"), "#!/non-existent/interpreter\n").unwrap();
+
+        with_scoped_env(&[("PATH", dir.as_str())], || {
+            assert_eq!(
+                resolve_exec_chain("fake-script"),
+                Some(vec![
+                    dir.join("fake-script"),
+                    dir.join("fake-interpreter"),
+                    native.clone()
+                ])
+            );
+            assert_eq!(
+                resolve_exec_chain("fake-native"),
+                Some(vec![native.clone()])
+            );
+            assert_eq!(resolve_exec_chain("fake-loop"), None);
+            assert_eq!(resolve_exec_chain("fake-broken"), None);
+            assert_eq!(resolve_exec_chain("non-existent-binary"), None);
+        });
+    }
+}
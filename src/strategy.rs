@@ -0,0 +1,163 @@
+//! Pluggable orderings of the PATH and environment variable sources.
+
+use camino::Utf8PathBuf;
+
+use crate::{find_in_env, path_dirs, probe_for_binary, trim_exec};
+
+/// How [`find_with_strategy`] combines the PATH directories and the environment variable named
+/// after the executable, e.g. to interleave them.
+///
+/// `resolve` gets the primitives the built-in search is made of: the PATH directories in order and
+/// `probe`, which checks a candidate path with the usual extension logic (see
+/// [`probe_for_binary`]). The environment variable can be read with
+/// [`find_in_env`](crate::find_in_env).
+///
+/// ```
+/// use camino::Utf8PathBuf;
+/// use executable_path_finder::{find_with_strategy, SourceStrategy};
+///
+/// // Search PATH from the last entry to the first.
+/// struct Reversed;
+///
+/// impl SourceStrategy for Reversed {
+///     fn resolve(
+///         &self,
+///         exec: &str,
+///         dirs: &[Utf8PathBuf],
+///         probe: &dyn Fn(Utf8PathBuf) -> Option<Utf8PathBuf>,
+///     ) -> Option<Utf8PathBuf> {
+///         dirs.iter().rev().find_map(|dir| probe(dir.join(exec)))
+///     }
+/// }
+///
+/// println!("{:?}", find_with_strategy("cargo", &Reversed));
+/// ```
+pub trait SourceStrategy {
+    /// resolve return the match for `exec` among `dirs` and the environment, using `probe` to check
+    /// candidate paths.
+    fn resolve(
+        &self,
+        exec: &str,
+        dirs: &[Utf8PathBuf],
+        probe: &dyn Fn(Utf8PathBuf) -> Option<Utf8PathBuf>,
+    ) -> Option<Utf8PathBuf>;
+}
+
+/// PATH first, then the environment variable, like [`find`](crate::find).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PathThenEnv;
+
+impl SourceStrategy for PathThenEnv {
+    fn resolve(
+        &self,
+        exec: &str,
+        dirs: &[Utf8PathBuf],
+        probe: &dyn Fn(Utf8PathBuf) -> Option<Utf8PathBuf>,
+    ) -> Option<Utf8PathBuf> {
+        dirs.iter()
+            .find_map(|dir| probe(dir.join(exec)))
+            .or_else(|| find_in_env(exec))
+    }
+}
+
+/// The environment variable first, as an override of PATH, then PATH.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvThenPath;
+
+impl SourceStrategy for EnvThenPath {
+    fn resolve(
+        &self,
+        exec: &str,
+        dirs: &[Utf8PathBuf],
+        probe: &dyn Fn(Utf8PathBuf) -> Option<Utf8PathBuf>,
+    ) -> Option<Utf8PathBuf> {
+        find_in_env(exec).or_else(|| dirs.iter().find_map(|dir| probe(dir.join(exec))))
+    }
+}
+
+/// PATH order, except that the environment variable wins in the directory it points into.
+///
+/// For each PATH directory, the path from the environment variable is used if it lives directly
+/// in that directory and passes `probe`; otherwise the directory is probed for `exec`. The
+/// variable is ignored when it points outside of PATH.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvInPath;
+
+impl SourceStrategy for EnvInPath {
+    fn resolve(
+        &self,
+        exec: &str,
+        dirs: &[Utf8PathBuf],
+        probe: &dyn Fn(Utf8PathBuf) -> Option<Utf8PathBuf>,
+    ) -> Option<Utf8PathBuf> {
+        let env = find_in_env(exec);
+        dirs.iter().find_map(|dir| {
+            env.as_ref()
+                .filter(|it| it.parent() == Some(dir.as_path()))
+                .and_then(|it| probe(it.clone()))
+                .or_else(|| probe(dir.join(exec)))
+        })
+    }
+}
+
+/// find_with_strategy return the executable resolved by `strategy` from PATH and the environment,
+/// see [`SourceStrategy`]. [`PathThenEnv`] gives the same result as [`find`](crate::find).
+pub fn find_with_strategy(exec: &str, strategy: &impl SourceStrategy) -> Option<Utf8PathBuf> {
+    strategy.resolve(trim_exec(exec), &path_dirs(), &probe_for_binary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use std::{env, fs};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_with_strategy() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let suffix = env::consts::EXE_SUFFIX;
+        let binary = dir.join(format!("fake-strategy{suffix}"));
+        let alternative = dir.join(format!("fake-strategy-alt{suffix}"));
+        fs::write(&binary, "").unwrap();
+        fs::write(&alternative, "").unwrap();
+
+        let paths = env::join_paths([dir.join("missing"), dir.clone()]).unwrap();
+        let vars = [
+            ("PATH", paths.to_str().unwrap()),
+            ("FAKE-STRATEGY", alternative.as_str()),
+        ];
+        with_scoped_env(&vars, || {
+            assert_eq!(
+                find_with_strategy("fake-strategy", &PathThenEnv),
+                Some(binary.clone())
+            );
+            assert_eq!(
+                find_with_strategy("fake-strategy", &EnvThenPath),
+                Some(alternative.clone())
+            );
+            assert_eq!(
+                find_with_strategy("fake-strategy", &EnvInPath),
+                Some(alternative.clone())
+            );
+        });
+
+        let vars = [
+            ("PATH", dir.as_str()),
+            ("FAKE-STRATEGY", "/outside/fake-strategy"),
+        ];
+        with_scoped_env(&vars, || {
+            assert_eq!(
+                find_with_strategy("fake-strategy", &EnvInPath),
+                Some(binary.clone())
+            );
+            assert_eq!(
+                find_with_strategy("fake-strategy", &EnvThenPath),
+                Some("/outside/fake-strategy".into())
+            );
+        });
+    }
+}
@@ -0,0 +1,106 @@
+//! Streaming PATH matches from a Tokio runtime.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    vec,
+};
+
+use camino::Utf8PathBuf;
+use futures_core::Stream;
+use tokio::task::JoinHandle;
+
+use crate::{path_dirs, probe_for_binary, trim_exec};
+
+/// find_all_stream return a stream of every match for `exec` on PATH, like
+/// [`find_all`](crate::find_all), yielding each one as soon as it is probed, e.g. to fill a list
+/// incrementally while a slow network mount is searched.
+///
+/// PATH is read when this is called. Its directories are then probed one at a time, in PATH order,
+/// on Tokio's blocking thread pool, so matches come in PATH order and the next directory is only
+/// probed once the stream is polled again. Dropping the stream cancels the search: a probe already
+/// running finishes in the background and its result is discarded, and no further directory is
+/// probed. The stream must be polled from within a Tokio runtime.
+pub fn find_all_stream(exec: &str) -> impl Stream<Item = Utf8PathBuf> + Send + Unpin + 'static {
+    MatchStream {
+        exec: trim_exec(exec).to_owned(),
+        dirs: path_dirs().into_iter(),
+        seen: Vec::new(),
+        probe: None,
+    }
+}
+
+struct MatchStream {
+    exec: String,
+    dirs: vec::IntoIter<Utf8PathBuf>,
+    seen: Vec<Utf8PathBuf>,
+    probe: Option<JoinHandle<Option<Utf8PathBuf>>>,
+}
+
+impl Stream for MatchStream {
+    type Item = Utf8PathBuf;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(probe) = &mut this.probe {
+                let found = ready!(Pin::new(probe).poll(cx));
+                this.probe = None;
+                // A probe that panicked is treated like a miss.
+                if let Ok(Some(path)) = found {
+                    if !this.seen.contains(&path) {
+                        this.seen.push(path.clone());
+                        return Poll::Ready(Some(path));
+                    }
+                }
+            }
+            let Some(dir) = this.dirs.next() else {
+                return Poll::Ready(None);
+            };
+            let candidate = dir.join(&this.exec);
+            this.probe = Some(tokio::task::spawn_blocking(move || {
+                probe_for_binary(candidate)
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use std::{env, fs};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_all_stream() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let [first, empty, second] = [dir.join("first"), dir.join("empty"), dir.join("second")];
+        let name = format!("fake-streamed{}", env::consts::EXE_SUFFIX);
+        for sub in [&first, &empty, &second] {
+            fs::create_dir(sub).unwrap();
+        }
+        fs::write(first.join(&name), "").unwrap();
+        fs::write(second.join(&name), "").unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let paths = env::join_paths([&first, &empty, &second]).unwrap();
+        let mut stream = with_scoped_env(&[("PATH", paths.to_str().unwrap())], || {
+            find_all_stream("fake-streamed")
+        });
+        let mut next = || {
+            runtime.block_on(std::future::poll_fn(|cx| {
+                Pin::new(&mut stream).poll_next(cx)
+            }))
+        };
+        assert_eq!(next(), Some(first.join(&name)));
+        assert_eq!(next(), Some(second.join(&name)));
+        assert_eq!(next(), None);
+    }
+}
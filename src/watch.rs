@@ -0,0 +1,115 @@
+//! A cached lookup that notices executables installed or removed while it lives.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use camino::Utf8PathBuf;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{find_in_env, path_dirs, probe_for_binary, trim_exec};
+
+type Cache = Arc<Mutex<HashMap<String, Option<Utf8PathBuf>>>>;
+
+/// WatchingFinder searches PATH and then the environment variable like [`find`](crate::find),
+/// caching the PATH results until a PATH directory changes, for long-lived processes such as
+/// language servers.
+///
+/// The PATH directories are read once, when the WatchingFinder is created, and each is watched
+/// with [`notify`], so the platform support is notify's (inotify on Linux, FSEvents on macOS,
+/// `ReadDirectoryChangesW` on Windows, ...). Setting up the watch costs one watch per directory;
+/// directories that don't exist or can't be watched keep being searched, but changes to them go
+/// unnoticed. Any event in a watched directory clears the whole cache, and events are delivered
+/// asynchronously, so a binary installed a moment ago may be missed until the event arrives.
+/// The environment variable is read on every lookup.
+///
+/// ```
+/// use executable_path_finder::WatchingFinder;
+///
+/// let finder = WatchingFinder::new().unwrap();
+/// println!("{:?}", finder.find("cargo"));
+/// ```
+pub struct WatchingFinder {
+    dirs: Vec<Utf8PathBuf>,
+    cache: Cache,
+    _watcher: RecommendedWatcher,
+}
+
+impl WatchingFinder {
+    /// Watch the current PATH directories. Errors creating the watcher itself are returned.
+    pub fn new() -> notify::Result<Self> {
+        let dirs = path_dirs();
+        let cache = Cache::default();
+        let events_cache = Arc::clone(&cache);
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                if event.is_ok() {
+                    lock(&events_cache).clear();
+                }
+            })?;
+        for dir in &dirs {
+            // A missing or unreadable directory is still searched, just not watched.
+            let _ = watcher.watch(dir.as_std_path(), RecursiveMode::NonRecursive);
+        }
+        Ok(WatchingFinder {
+            dirs,
+            cache,
+            _watcher: watcher,
+        })
+    }
+
+    /// find return a `Utf8PathBuf` for the given executable, from the cache when PATH hasn't
+    /// changed since the last lookup of `exec`.
+    pub fn find(&self, exec: &str) -> Option<Utf8PathBuf> {
+        let exec = trim_exec(exec);
+        let cached = lock(&self.cache).get(exec).cloned();
+        let found = match cached {
+            Some(found) => found,
+            None => {
+                let found = self
+                    .dirs
+                    .iter()
+                    .find_map(|dir| probe_for_binary(dir.join(exec)));
+                lock(&self.cache).insert(exec.to_owned(), found.clone());
+                found
+            }
+        };
+        found.or_else(|| find_in_env(exec))
+    }
+}
+
+fn lock(cache: &Cache) -> MutexGuard<'_, HashMap<String, Option<Utf8PathBuf>>> {
+    // Entries are inserted whole, so a panic while holding the lock leaves the map usable.
+    cache.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::lock_env;
+    use crate::with_scoped_env;
+    use std::{
+        env, fs,
+        time::{Duration, Instant},
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_watching_finder() {
+        let _guard = lock_env();
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Utf8PathBuf::try_from(temp_dir.path().to_path_buf()).unwrap();
+        let binary = dir.join(format!("fake-watched-binary{}", env::consts::EXE_SUFFIX));
+
+        let finder = with_scoped_env(&[("PATH", dir.as_str())], || WatchingFinder::new().unwrap());
+        assert_eq!(finder.find("fake-watched-binary"), None);
+
+        fs::write(&binary, "").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while finder.find("fake-watched-binary").is_none() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(finder.find("fake-watched-binary"), Some(binary));
+    }
+}